@@ -0,0 +1,317 @@
+use crate::rover::{self, Coordinates, Vehicle};
+use crate::terrain::Terrain;
+use crate::{bytecode, Config, ParseError};
+use core::fmt;
+
+/// Something a rover program would do wrong if it were run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViolationKind {
+    LeavesPlateau,
+    HitsObstacle,
+    /// The rover the program would hit, or `None` for one that was already on the
+    /// plateau before the mission.
+    HitsRover(Option<usize>),
+    SqueezesBetweenObstacles,
+    ExceedsBudget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Violation {
+    pub rover: usize,
+    /// Index of the offending command among those the rover executes, or `None` if the
+    /// rover cannot even land.
+    pub command: Option<usize>,
+    pub coordinates: Coordinates,
+    pub kind: ViolationKind,
+}
+
+impl Violation {
+    /// Every rover of the mission involved, starting with the one at fault.
+    pub fn rovers(&self) -> Vec<usize> {
+        match self.kind {
+            ViolationKind::HitsRover(Some(other)) => vec![self.rover, other],
+            _ => vec![self.rover],
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.command {
+            Some(command) => write!(f, "rover {} command {}: ", self.rover, command)?,
+            None => write!(f, "rover {} landing: ", self.rover)?,
+        }
+        // Steps below zero are reported wrapped around, so read coordinates as signed to
+        // show them as -1.
+        let at = format!(
+            "{} {}",
+            self.coordinates.x_coordinate as i64, self.coordinates.y_coordinate as i64
+        );
+        match self.kind {
+            ViolationKind::LeavesPlateau => write!(f, "leaves the plateau at {}", at),
+            ViolationKind::HitsObstacle => write!(f, "hits an obstacle at {}", at),
+            ViolationKind::HitsRover(Some(other)) => write!(f, "hits rover {} at {}", other, at),
+            ViolationKind::HitsRover(None) => write!(f, "hits a rover at {}", at),
+            ViolationKind::SqueezesBetweenObstacles => {
+                write!(f, "squeezes between obstacles into {}", at)
+            }
+            ViolationKind::ExceedsBudget => write!(f, "runs out of instruction budget at {}", at),
+        }
+    }
+}
+
+/// Predicts every way the mission would go wrong on the given terrain, without touching
+/// it. Obstacles that cannot all be placed, such as two in the same cell, are an error. Rovers are simulated in landing order on a copy of the terrain; a rover whose move
+/// would be a violation stays where it is and carries on with its next command, so one
+/// mistake does not hide the ones after it. A rover that cannot land is left out of the
+/// rest of the simulation.
+pub fn analyse<T: Terrain + Clone, V: Vehicle>(
+    config: &Config,
+    terrain: &T,
+) -> Result<Vec<Violation>, ParseError> {
+    config.check::<V>()?;
+
+    let mut terrain = terrain.clone();
+    let mut violations = vec![];
+    let mut landed: Vec<(usize, Coordinates)> = vec![];
+
+    for obstacle in &config.obstacles {
+        terrain.place_obstacle(*obstacle).map_err(|_| {
+            let details = format!(
+                "could not place obstacle at {} {}",
+                obstacle.x_coordinate, obstacle.y_coordinate
+            );
+            ParseError::new(&details)
+        })?;
+    }
+
+    for (id, instruction) in config.instructions.iter().enumerate() {
+        // Bearings were checked above, so landing cannot fail.
+        let mut rover = V::land(
+            instruction.starting_x,
+            instruction.starting_y,
+            instruction.bearing,
+        )
+        .map_err(|e| ParseError::new(&e))?;
+        let start = *rover.coordinates();
+        if let Some(kind) = obstruction(&terrain, &landed, &start) {
+            violations.push(Violation {
+                rover: id,
+                command: None,
+                coordinates: start,
+                kind,
+            });
+            continue;
+        }
+        let _ = terrain.drop_rover(start);
+
+        let code = bytecode::compile(&instruction.program);
+        let mut vm = bytecode::Vm::new(&code, config.budget(&instruction.program));
+        let mut index = 0;
+        loop {
+            let command = match vm.next_command(|sensor| crate::sense(sensor, &rover, &terrain)) {
+                Ok(Some(command)) => command,
+                Ok(None) => break,
+                Err(_) => {
+                    violations.push(Violation {
+                        rover: id,
+                        command: Some(index),
+                        coordinates: *rover.coordinates(),
+                        kind: ViolationKind::ExceedsBudget,
+                    });
+                    break;
+                }
+            };
+            let step = match rover.planned_step(&command) {
+                Some(step) => step,
+                None => {
+                    rover.execute_command(&command);
+                    index += 1;
+                    continue;
+                }
+            };
+            let planned = match terrain.resolve(rover.coordinates(), step) {
+                Some(planned) => planned,
+                None => {
+                    violations.push(Violation {
+                        rover: id,
+                        command: Some(index),
+                        coordinates: off_grid(rover.coordinates(), step),
+                        kind: ViolationKind::LeavesPlateau,
+                    });
+                    index += 1;
+                    continue;
+                }
+            };
+            let squeeze = match config.compass {
+                rover::Compass::EightPoint {
+                    allow_squeeze: false,
+                } => terrain
+                    .can_rover_squeeze(rover.coordinates(), &planned)
+                    .is_err(),
+                _ => false,
+            };
+            let kind = obstruction(&terrain, &landed, &planned)
+                .or(Some(ViolationKind::SqueezesBetweenObstacles).filter(|_| squeeze));
+            match kind {
+                Some(kind) => violations.push(Violation {
+                    rover: id,
+                    command: Some(index),
+                    coordinates: planned,
+                    kind,
+                }),
+                None => {
+                    let _ = terrain.update_rover_position(rover.coordinates(), &planned);
+                    rover.move_to(planned);
+                }
+            }
+            index += 1;
+        }
+
+        landed.push((id, *rover.coordinates()));
+    }
+
+    Ok(violations)
+}
+
+/// Where a step below zero or past `u64::MAX` was headed. There is no such cell, so the
+/// coordinate wraps around, and `Violation` shows a step below zero as -1.
+fn off_grid(from: &Coordinates, step: (i64, i64)) -> Coordinates {
+    Coordinates::new(
+        from.x_coordinate.wrapping_add(step.0 as u64),
+        from.y_coordinate.wrapping_add(step.1 as u64),
+    )
+}
+
+/// What, if anything, stops a rover from standing on the cell.
+fn obstruction<T: Terrain>(
+    terrain: &T,
+    landed: &[(usize, Coordinates)],
+    coordinates: &Coordinates,
+) -> Option<ViolationKind> {
+    if !terrain.is_inbounds(coordinates) {
+        Some(ViolationKind::LeavesPlateau)
+    } else if terrain.has_obstacle(coordinates) {
+        Some(ViolationKind::HitsObstacle)
+    } else if terrain.is_occupied(coordinates) {
+        Some(ViolationKind::HitsRover(
+            landed
+                .iter()
+                .find(|(_, landed)| landed == coordinates)
+                .map(|(id, _)| *id),
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::{HexPlateau, HexRover};
+    use crate::plateau::Plateau;
+    use crate::rover::Rover;
+    use crate::terrain::ToroidalPlateau;
+
+    fn config(rovers: &[&str]) -> Config {
+        let mut args = vec!["test", "5", "5"];
+        for rover in rovers {
+            args.extend(rover.split(' '));
+        }
+        let args: Vec<String> = args.into_iter().map(String::from).collect();
+        Config::new(&args).expect("should create config")
+    }
+
+    #[test]
+    fn safe_mission_has_no_violations() {
+        let config = config(&["1 2 N LMLMLMLMM", "3 3 E MMRMMRMRRM"]);
+
+        assert_eq!(
+            Ok(vec![]),
+            analyse::<_, Rover>(&config, &Plateau::new(5, 5))
+        );
+    }
+
+    #[test]
+    fn report_every_violation() {
+        let config = config(&["0 0 N MMM", "2 5 N MRRM"]).with_obstacle(Coordinates::new(0, 3));
+        let plateau = Plateau::new(5, 5);
+
+        let violations = analyse::<_, Rover>(&config, &plateau).expect("should analyse");
+
+        assert_eq!(
+            vec![
+                Violation {
+                    rover: 0,
+                    command: Some(2),
+                    coordinates: Coordinates::new(0, 3),
+                    kind: ViolationKind::HitsObstacle,
+                },
+                Violation {
+                    rover: 1,
+                    command: Some(0),
+                    coordinates: Coordinates::new(2, 6),
+                    kind: ViolationKind::LeavesPlateau,
+                },
+            ],
+            violations
+        );
+    }
+
+    #[test]
+    fn name_the_rovers_involved() {
+        let config = config(&["1 1 N M", "1 3 S LRM"]);
+
+        let violations = analyse::<_, Rover>(&config, &Plateau::new(5, 5)).expect("should analyse");
+
+        assert_eq!(1, violations.len());
+        assert_eq!(ViolationKind::HitsRover(Some(0)), violations[0].kind);
+        assert_eq!(vec![1, 0], violations[0].rovers());
+        assert_eq!(
+            "rover 1 command 2: hits rover 0 at 1 2",
+            violations[0].to_string()
+        );
+    }
+
+    #[test]
+    fn leave_the_plateau_untouched() {
+        let config = config(&["1 1 N M"]);
+        let mut plateau = Plateau::new(5, 5);
+        plateau.drop_rover(Coordinates::new(1, 2)).unwrap();
+
+        let violations = analyse::<_, Rover>(&config, &plateau).expect("should analyse");
+
+        assert_eq!(ViolationKind::HitsRover(None), violations[0].kind);
+        assert_eq!(vec![Coordinates::new(1, 2)], plateau.list_rovers());
+    }
+
+    #[test]
+    fn reject_obstacles_that_cannot_be_placed() {
+        let config = config(&["1 1 N M"])
+            .with_obstacle(Coordinates::new(3, 3))
+            .with_obstacle(Coordinates::new(3, 3));
+
+        assert_eq!(
+            "could not place obstacle at 3 3",
+            analyse::<_, Rover>(&config, &Plateau::new(5, 5))
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn follow_the_terrain_and_vehicle_the_mission_runs_with() {
+        let wrapping = config(&["0 0 S M"]);
+        let diagonal = config(&["1 1 SE M"]);
+
+        assert_eq!(
+            Ok(vec![]),
+            analyse::<_, Rover>(&wrapping, &ToroidalPlateau::new(5, 5))
+        );
+        assert_eq!(
+            Ok(vec![]),
+            analyse::<_, HexRover>(&diagonal, &HexPlateau::new(5, 5))
+        );
+        assert!(analyse::<_, Rover>(&diagonal, &Plateau::new(5, 5)).is_err());
+    }
+}
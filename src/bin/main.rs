@@ -1,29 +1,633 @@
 extern crate mars_rover;
 
+use mars_rover::analysis::ViolationKind;
+use mars_rover::camera::{Camera, FieldOfView};
+use mars_rover::hex::{HexPlateau, HexRover};
 pub use mars_rover::plateau;
 pub use mars_rover::rover;
+use mars_rover::rover::Vehicle;
+use mars_rover::terrain::{Terrain, ToroidalPlateau};
 pub use mars_rover::Config;
+use mars_rover::{lint, mission, ParseError, Status};
 use std::env;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+
+const USAGE: &str = "\
+usage: main [command] [options] [plateau and rovers]
+
+commands:
+  run        deploy the rovers and print where they end up (the default)
+  validate   report every problem with a mission without running it
+  plan       predict what would go wrong, or show the compiled programs
+  render     deploy the rovers and draw the plateau
+  replay     drive the last rover on with a recorded command log
+  trace      deploy the rovers and write every command they carry out
+  repl       land and drive rovers interactively, one line at a time
+  help       print this message
+
+options:
+  --input <path>        read the mission from a file instead of the arguments
+  --format <format>     mission format: text, json or toml (default: from extension)
+  --output <format>     output format: line, text or json (default: line)
+  --eight-point         use an eight point compass
+  --allow-squeeze       with --eight-point, let rovers move diagonally between obstacles
+  --hex                 drive hex rovers on a hex plateau
+  --toroidal            wrap rovers around the edges of the plateau
+  --optimise            shorten rover programs before running them
+  --camera <range>[:cone]
+                        fit every rover with a camera seeing that far, all around or
+                        in a cone ahead (default: all around)
+  --photo-log <path>    write the photos taken to a CSV or JSON file
+  --trace <path>        write a trace to a CSV or NDJSON file
+  --disassemble         with plan, print the compiled programs
+  --log <path>          with replay, the command log to read (default: -)
+  -h, --help            print this message
+
+exit codes:
+  0  success
+  1  a rover ran out of instruction budget
+  2  the command line or mission could not be parsed
+  3  a rover collided with another rover or an obstacle
+  4  a rover left the plateau
+  5  a file could not be read or written
+";
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_PARSE: i32 = 2;
+const EXIT_COLLISION: i32 = 3;
+const EXIT_OUT_OF_BOUNDS: i32 = 4;
+const EXIT_IO: i32 = 5;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let config = match Config::new(&args) {
-        Ok(val) => val,
-        Err(e) => {
-            eprintln!("could not create config : {}", e);
-            std::process::exit(1)
+    let mut args: Vec<String> = env::args().collect();
+    if take_flag(&mut args, "--help") || take_flag(&mut args, "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+    let command = match first_operand(&args) {
+        Some(index) if args[index] == "help" => {
+            print!("{}", USAGE);
+            return;
         }
+        Some(index) if COMMANDS.contains(&args[index].as_str()) => args.remove(index),
+        _ => String::from("run"),
     };
+    check_options(&args, &command);
 
-    let rovers = match mars_rover::deploy_rovers(config) {
+    let input = take_option(&mut args, "--input");
+    let format = match take_option(&mut args, "--format").as_deref() {
+        None => input.as_deref().map(mission::Format::from_path),
+        Some("text") => Some(mission::Format::Text),
+        Some("json") => Some(mission::Format::Json),
+        Some("toml") => Some(mission::Format::Toml),
+        Some(other) => fail(&format!("unknown mission format : {}", other), EXIT_PARSE),
+    };
+    let output = match take_option(&mut args, "--output").as_deref() {
+        None | Some("line") => Output::Line,
+        Some("text") => Output::Text,
+        Some("json") => Output::Json,
+        Some(other) => fail(&format!("unknown output format : {}", other), EXIT_PARSE),
+    };
+    let camera = take_option(&mut args, "--camera").map(|spec| camera(&spec));
+    let photo_log = take_option(&mut args, "--photo-log");
+    let trace = take_option(&mut args, "--trace");
+    let log = take_option(&mut args, "--log");
+    let eight_point = take_flag(&mut args, "--eight-point");
+    let allow_squeeze = take_flag(&mut args, "--allow-squeeze");
+    let hex = take_flag(&mut args, "--hex");
+    let toroidal = take_flag(&mut args, "--toroidal");
+    let disassemble = take_flag(&mut args, "--disassemble");
+    let optimise = take_flag(&mut args, "--optimise");
+    if allow_squeeze && !eight_point {
+        fail(
+            "--allow-squeeze needs --eight-point (see --help)",
+            EXIT_PARSE,
+        )
+    }
+    let command = command.as_str();
+
+    let compass = if eight_point {
+        rover::Compass::EightPoint { allow_squeeze }
+    } else {
+        rover::Compass::FourPoint
+    };
+    let ground = if hex {
+        Ground::Hex
+    } else if toroidal {
+        Ground::Toroidal
+    } else {
+        Ground::Square
+    };
+
+    if command == "repl" {
+        repl(compass);
+        return;
+    }
+
+    if command == "validate" {
+        match input.as_deref().or_else(|| args.get(1).map(String::as_str)) {
+            Some(path) => validate(path, format, compass, ground),
+            None => fail("validate needs a mission (see --help)", EXIT_PARSE),
+        }
+    }
+
+    let config = match (input, format) {
+        (Some(path), Some(format)) => read_mission(&path, format),
+        _ => Config::new(&args),
+    };
+    let config = match config {
+        Ok(val) if eight_point => val.with_compass(compass),
         Ok(val) => val,
-        Err(e) => {
-            eprintln!("could not deploy rovers : {}", e);
-            std::process::exit(1)
+        Err(e) => fail(&format!("could not create config : {}", e), error_code(&*e)),
+    };
+    let config = match camera {
+        Some(camera) => config.with_camera(camera),
+        None => config,
+    };
+
+    match command {
+        "plan" => plan(&config, disassemble, ground),
+        "replay" => {
+            if let Output::Json = output {
+                fail("replay cannot write json output (see --help)", EXIT_PARSE)
+            }
+            let path = log.unwrap_or_else(|| String::from("-"));
+            let (max_x, max_y) = (config.max_x_grid(), config.max_y_grid());
+            match ground {
+                Ground::Hex => {
+                    replay::<_, HexRover>(HexPlateau::new(max_x, max_y), config, &path, output)
+                }
+                Ground::Toroidal => replay::<_, rover::Rover>(
+                    ToroidalPlateau::new(max_x, max_y),
+                    config,
+                    &path,
+                    output,
+                ),
+                Ground::Square => replay::<_, rover::Rover>(
+                    plateau::Plateau::new(max_x, max_y),
+                    config,
+                    &path,
+                    output,
+                ),
+            }
+        }
+        _ => {
+            let options = Options {
+                photo_log,
+                trace: match command {
+                    "trace" => Some(trace.unwrap_or_else(|| String::from("-"))),
+                    _ => trace,
+                },
+                optimise,
+                show_map: command == "render",
+                output,
+            };
+            let (max_x, max_y) = (config.max_x_grid(), config.max_y_grid());
+            match ground {
+                Ground::Hex => run::<_, HexRover>(HexPlateau::new(max_x, max_y), config, options),
+                Ground::Toroidal => {
+                    run::<_, rover::Rover>(ToroidalPlateau::new(max_x, max_y), config, options)
+                }
+                Ground::Square => {
+                    run::<_, rover::Rover>(plateau::Plateau::new(max_x, max_y), config, options)
+                }
+            }
+        }
+    }
+}
+
+/// The terrain and vehicle every subcommand uses, so that `plan` and `validate` predict
+/// what `run` would do.
+#[derive(Clone, Copy)]
+enum Ground {
+    Square,
+    Toroidal,
+    Hex,
+}
+
+enum Output {
+    /// Every rover on one line, as the binary has always printed them.
+    Line,
+    /// One rover per line, like the challenge's expected output.
+    Text,
+    /// Each rover's id, final pose and outcome. Rovers that fail stop where they are
+    /// rather than ending the mission.
+    Json,
+}
+
+struct Options {
+    photo_log: Option<String>,
+    /// Where to write the trace, or `-` for standard output.
+    trace: Option<String>,
+    optimise: bool,
+    show_map: bool,
+    output: Output,
+}
+
+/// Prints the message and exits with the given code.
+fn fail(message: &str, code: i32) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(code)
+}
+
+/// The exit code for a mission that ended with the given status.
+fn exit_code(status: Status) -> i32 {
+    match status {
+        Status::Ok => 0,
+        Status::Collided => EXIT_COLLISION,
+        Status::Lost => EXIT_OUT_OF_BOUNDS,
+        Status::Aborted => EXIT_FAILURE,
+    }
+}
+
+/// The exit code for an error that stopped the binary.
+fn error_code(error: &(dyn Error + 'static)) -> i32 {
+    if error.is::<io::Error>() {
+        EXIT_IO
+    } else if error.is::<ParseError>() {
+        EXIT_PARSE
+    } else {
+        exit_code(Status::of(error))
+    }
+}
+
+/// Reads a camera given as its range, optionally followed by `:cone` or `:radius`.
+fn camera(spec: &str) -> Camera {
+    let (range, field_of_view) = match spec.split_once(':') {
+        None => (spec, FieldOfView::Radius),
+        Some((range, "radius")) => (range, FieldOfView::Radius),
+        Some((range, "cone")) => (range, FieldOfView::Cone),
+        Some(_) => fail(&format!("unknown camera : {}", spec), EXIT_PARSE),
+    };
+    match range.parse() {
+        Ok(range) => Camera::new(range, field_of_view),
+        Err(_) => fail(&format!("unknown camera : {}", spec), EXIT_PARSE),
+    }
+}
+
+fn read_mission(path: &str, format: mission::Format) -> Result<Config, Box<dyn Error>> {
+    let source = fs::read_to_string(path)?;
+    match format {
+        mission::Format::Json => Ok(mission::parse_json(&source)?),
+        mission::Format::Toml => Ok(mission::parse_toml(&source)?),
+        mission::Format::Text => Ok(mission::parse(&source)?),
+    }
+}
+
+/// Reports every problem with a mission file without running it, then exits with the
+/// code of the first error found, or 0 when there are only warnings.
+fn validate(
+    path: &str,
+    format: Option<mission::Format>,
+    compass: rover::Compass,
+    ground: Ground,
+) -> ! {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => fail(&format!("could not read mission : {}", e), EXIT_IO),
+    };
+    let format = format.unwrap_or_else(|| mission::Format::from_path(path));
+    let diagnostics = match ground {
+        Ground::Square => {
+            lint::lint::<_, rover::Rover>(&source, format, compass, plateau::Plateau::new)
+        }
+        Ground::Toroidal => {
+            lint::lint::<_, rover::Rover>(&source, format, compass, ToroidalPlateau::new)
+        }
+        Ground::Hex => lint::lint::<_, HexRover>(&source, format, compass, HexPlateau::new),
+    };
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    let errors: Vec<&lint::Diagnostic> = diagnostics
+        .iter()
+        .filter(|d| d.severity == lint::Severity::Error)
+        .collect();
+    println!(
+        "{} error(s), {} warning(s)",
+        errors.len(),
+        diagnostics.len() - errors.len()
+    );
+    std::process::exit(match errors.first().map(|d| d.violation) {
+        None => 0,
+        Some(None) => EXIT_PARSE,
+        Some(Some(ViolationKind::LeavesPlateau)) => EXIT_OUT_OF_BOUNDS,
+        Some(Some(ViolationKind::ExceedsBudget)) => EXIT_FAILURE,
+        Some(Some(_)) => EXIT_COLLISION,
+    })
+}
+
+/// Prints every predicted violation, or the compiled rover programs, without running the
+/// mission.
+fn plan(config: &Config, disassemble: bool, ground: Ground) {
+    if disassemble {
+        for (rover, code) in config.bytecode().iter().enumerate() {
+            println!("rover {}:\n{}", rover, code.disassemble());
+        }
+        return;
+    }
+    let (max_x, max_y) = (config.max_x_grid(), config.max_y_grid());
+    let violations = match ground {
+        Ground::Square => config.analyse::<_, rover::Rover>(&plateau::Plateau::new(max_x, max_y)),
+        Ground::Toroidal => config.analyse::<_, rover::Rover>(&ToroidalPlateau::new(max_x, max_y)),
+        Ground::Hex => config.analyse::<_, HexRover>(&HexPlateau::new(max_x, max_y)),
+    };
+    match violations {
+        Ok(violations) => {
+            for violation in &violations {
+                println!("{}", violation);
+            }
+            if let Some(violation) = violations.first() {
+                std::process::exit(match violation.kind {
+                    ViolationKind::LeavesPlateau => EXIT_OUT_OF_BOUNDS,
+                    ViolationKind::ExceedsBudget => EXIT_FAILURE,
+                    _ => EXIT_COLLISION,
+                })
+            }
+        }
+        Err(e) => fail(&format!("could not analyse mission : {}", e), EXIT_PARSE),
+    }
+}
+
+/// Reads commands from standard input until it ends or the operator quits, showing the
+/// plateau again whenever a rover is stopped.
+fn repl(compass: rover::Compass) {
+    let mut session = mars_rover::repl::Session::with_compass(compass);
+    println!("type 'help' for commands, 'quit' to leave");
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        io::stdout().flush().ok();
+        line.clear();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => {
+                println!();
+                break;
+            }
+            Ok(_) => {}
+            Err(e) => fail(&format!("could not read input : {}", e), EXIT_IO),
         }
+        match line.trim() {
+            "quit" | "exit" => break,
+            line => match session.execute(line) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => println!("{}", output),
+                Err(e) => {
+                    println!("error: {}", e);
+                    if Status::of(&*e) != Status::Aborted {
+                        println!("{}", session.map());
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Replays a recorded command log for the last rover, reading standard input when the
+/// path is `-`.
+fn replay<T: Terrain, V: Vehicle>(terrain: T, config: Config, path: &str, output: Output) {
+    let last = config.rovers().saturating_sub(1);
+    let result: Result<Vec<V>, _> = if path == "-" {
+        mars_rover::replay(terrain, config, last, io::stdin().lock())
+    } else {
+        File::open(path)
+            .map_err(|e| e.into())
+            .and_then(|file| mars_rover::replay(terrain, config, last, file))
     };
+    match result {
+        Ok(rovers) => {
+            for rover in rovers {
+                match output {
+                    Output::Text => println!("{}", rover),
+                    _ => print!("{} ", rover),
+                }
+            }
+        }
+        Err(e) => fail(
+            &format!("could not replay command log : {}", e),
+            error_code(&*e),
+        ),
+    }
+}
+
+fn run<T: Terrain, V: Vehicle>(terrain: T, mut config: Config, options: Options) {
+    if options.optimise {
+        match config.optimise::<V>() {
+            Ok(savings) => {
+                for (rover, savings) in savings.iter().enumerate() {
+                    eprintln!("rover {}: {}", rover, savings);
+                }
+            }
+            Err(e) => fail(
+                &format!("could not optimise rover programs : {}", e),
+                EXIT_PARSE,
+            ),
+        }
+    }
+
+    if options.trace.is_some() {
+        config = config.with_trace();
+    }
+    config = config.without_coverage();
+
+    let ids: Vec<String> = (0..config.rovers())
+        .map(|rover| config.rover_name(rover))
+        .collect();
+    // A trace is most useful when a rover fails, so tracing keeps going past failures
+    // too.
+    let tolerant = matches!(options.output, Output::Json) || options.trace.is_some();
+    let deployment = if tolerant {
+        mars_rover::drive_tolerant::<T, V>(terrain, config)
+    } else {
+        mars_rover::drive::<T, V>(terrain, config)
+    };
+    let deployment = match deployment {
+        Ok(val) => val,
+        Err(e) => fail(&format!("could not deploy rovers : {}", e), error_code(&*e)),
+    };
+
+    if let Some(path) = options.photo_log {
+        let result = File::create(&path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            if path.ends_with(".json") {
+                deployment.photos().write_json(&mut writer)
+            } else {
+                deployment.photos().write_csv(&mut writer)
+            }
+        });
+        if let Err(e) = result {
+            fail(&format!("could not write photo log : {}", e), EXIT_IO)
+        }
+    }
+
+    let to_stdout = options.trace.as_deref() == Some("-");
+    if let Some(path) = &options.trace {
+        let result = if to_stdout {
+            write_trace(&deployment, io::stdout().lock(), &options.output)
+        } else {
+            File::create(path).and_then(|file| {
+                let ndjson = path.ends_with(".ndjson") || path.ends_with(".jsonl");
+                let output = if ndjson { Output::Json } else { Output::Line };
+                write_trace(&deployment, BufWriter::new(file), &output)
+            })
+        };
+        if let Err(e) = result {
+            fail(&format!("could not write trace : {}", e), EXIT_IO)
+        }
+    }
+
+    if !to_stdout {
+        match options.output {
+            Output::Json => print_json(&deployment, &ids),
+            Output::Line | Output::Text => {
+                if options.show_map {
+                    println!("{}", deployment.plateau().render());
+                }
+                for rover in deployment.rovers() {
+                    match options.output {
+                        Output::Text => println!("{}", rover),
+                        _ => print!("{} ", rover),
+                    }
+                }
+            }
+        }
+    }
+
+    let failed = deployment
+        .outcomes()
+        .iter()
+        .find(|outcome| outcome.status != Status::Ok);
+    if let Some(outcome) = failed {
+        io::stdout().flush().ok();
+        std::process::exit(exit_code(outcome.status))
+    }
+}
+
+/// Writes the trace as NDJSON for JSON output and as CSV otherwise.
+fn write_trace<T: Terrain, V: Vehicle, W: Write>(
+    deployment: &mars_rover::Deployment<T, V>,
+    mut writer: W,
+    output: &Output,
+) -> io::Result<()> {
+    match output {
+        Output::Json => deployment.trace().write_ndjson(&mut writer),
+        _ => deployment.trace().write_csv(&mut writer),
+    }
+}
+
+fn print_json<T: Terrain, V: Vehicle>(deployment: &mars_rover::Deployment<T, V>, ids: &[String]) {
+    let rovers: Vec<serde_json::Value> = deployment
+        .rovers()
+        .iter()
+        .zip(deployment.outcomes())
+        .zip(ids)
+        .map(|((rover, outcome), id)| {
+            serde_json::json!({
+                "id": id,
+                "x": rover.coordinates().x_coordinate,
+                "y": rover.coordinates().y_coordinate,
+                "bearing": rover.bearing().to_string(),
+                "status": outcome.status.to_string(),
+                "commands": outcome.commands,
+                "error": outcome.error,
+            })
+        })
+        .collect();
+    let output = serde_json::json!({ "rovers": rovers });
+    match serde_json::to_string_pretty(&output) {
+        Ok(json) => println!("{}", json),
+        Err(e) => fail(&format!("could not write output : {}", e), EXIT_FAILURE),
+    }
+}
+
+const COMMANDS: &[&str] = &[
+    "run", "validate", "plan", "render", "replay", "trace", "repl",
+];
+
+/// The subcommands that read a mission.
+const MISSION: &[&str] = &["run", "validate", "plan", "render", "replay", "trace"];
+
+/// The subcommands that deploy the rovers.
+const DEPLOY: &[&str] = &["run", "render", "trace"];
+
+/// Every option, whether it takes a value, and the subcommands it applies to.
+const OPTIONS: &[(&str, bool, &[&str])] = &[
+    ("--input", true, MISSION),
+    ("--format", true, MISSION),
+    ("--output", true, &["run", "render", "replay", "trace"]),
+    ("--eight-point", false, COMMANDS),
+    ("--allow-squeeze", false, COMMANDS),
+    ("--hex", false, MISSION),
+    ("--toroidal", false, MISSION),
+    ("--optimise", false, DEPLOY),
+    ("--camera", true, DEPLOY),
+    ("--photo-log", true, DEPLOY),
+    ("--trace", true, DEPLOY),
+    ("--disassemble", false, &["plan"]),
+    ("--log", true, &["replay"]),
+];
+
+/// Whether the word is an option rather than a value. A lone `-` names standard input.
+fn is_option(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('-')
+}
+
+/// Where the first word that is neither an option nor an option's value is, if any.
+fn first_operand(args: &[String]) -> Option<usize> {
+    let mut index = 1;
+    while index < args.len() {
+        match OPTIONS.iter().find(|(name, _, _)| *name == args[index]) {
+            Some((_, true, _)) => index += 2,
+            Some(_) => index += 1,
+            None if is_option(&args[index]) => index += 1,
+            None => return Some(index),
+        }
+    }
+    None
+}
+
+/// Fails with a parse error on an option the binary does not know, or one that does not
+/// apply to the subcommand, rather than ignoring it.
+fn check_options(args: &[String], command: &str) {
+    let mut index = 1;
+    while index < args.len() {
+        let word = &args[index];
+        index += 1;
+        if !is_option(word) {
+            continue;
+        }
+        match OPTIONS.iter().find(|(name, _, _)| name == word) {
+            None => fail(
+                &format!("unknown option : {} (see --help)", word),
+                EXIT_PARSE,
+            ),
+            Some((_, _, commands)) if !commands.contains(&command) => fail(
+                &format!("{} does not take {} (see --help)", command, word),
+                EXIT_PARSE,
+            ),
+            Some((_, takes_value, _)) => index += *takes_value as usize,
+        }
+    }
+}
+
+/// Removes `name` from the arguments, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != name);
+    args.len() != before
+}
 
-    for rover in rovers {
-        print!("{} ", rover);
+/// Removes `name` and the value following it from the arguments, returning the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let position = args.iter().position(|arg| arg == name)?;
+    args.remove(position);
+    if position < args.len() {
+        Some(args.remove(position))
+    } else {
+        None
     }
 }
@@ -0,0 +1,249 @@
+use crate::program::{BudgetExceeded, Condition, Instruction, Sensor};
+use crate::Command;
+use core::fmt;
+
+/// A single bytecode operation. Every operation except `Next` and `Jump` takes one step
+/// of the rover's budget, matching what the tree interpreter counts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Yields a command for the rover to execute.
+    Command(Command),
+    /// Does nothing but take a step, standing in for structure the interpreter walks over.
+    Step,
+    /// Starts a loop that runs its body the given number of times.
+    Repeat(u64),
+    /// Ends a loop body, jumping back to the given address while repetitions remain.
+    Next(usize),
+    /// Jumps to the given address when the condition does not hold.
+    JumpUnless(Condition, usize),
+    Jump(usize),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Op::Command(command) => write!(f, "COMMAND {}", command),
+            Op::Step => write!(f, "STEP"),
+            Op::Repeat(count) => write!(f, "REPEAT {}", count),
+            Op::Next(address) => write!(f, "NEXT {:04}", address),
+            Op::JumpUnless(condition, address) => {
+                write!(f, "JUMP_UNLESS {} {:04}", condition, address)
+            }
+            Op::Jump(address) => write!(f, "JUMP {:04}", address),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bytecode {
+    ops: Vec<Op>,
+}
+
+impl Bytecode {
+    pub fn ops(&self) -> &[Op] {
+        &self.ops
+    }
+
+    /// Lists the operations one per line, each prefixed with its address.
+    pub fn disassemble(&self) -> String {
+        self.ops
+            .iter()
+            .enumerate()
+            .map(|(address, op)| format!("{:04} {}", address, op))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Compiles a program with its macros already resolved.
+pub fn compile(program: &[Instruction]) -> Bytecode {
+    let mut code = Bytecode::default();
+    compile_into(program, &mut code.ops);
+    code
+}
+
+fn compile_into(program: &[Instruction], ops: &mut Vec<Op>) {
+    for instruction in program {
+        match instruction {
+            Instruction::Command(command) => ops.push(Op::Command(*command)),
+            Instruction::Repeat(body, count) if body.is_empty() || *count == 1 => {
+                ops.push(Op::Step);
+                compile_into(body, ops);
+            }
+            Instruction::Repeat(body, count) => {
+                ops.push(Op::Repeat(*count));
+                let start = ops.len();
+                compile_into(body, ops);
+                ops.push(Op::Next(start));
+            }
+            Instruction::If(condition, then, otherwise) => {
+                let branch = ops.len();
+                ops.push(Op::JumpUnless(*condition, 0));
+                compile_into(then, ops);
+                let jump = ops.len();
+                ops.push(Op::Jump(0));
+                ops[branch] = Op::JumpUnless(*condition, ops.len());
+                compile_into(otherwise, ops);
+                ops[jump] = Op::Jump(ops.len());
+            }
+            Instruction::While(condition, body) => {
+                ops.push(Op::Step);
+                let check = ops.len();
+                ops.push(Op::JumpUnless(*condition, 0));
+                compile_into(body, ops);
+                ops.push(Op::Jump(check));
+                ops[check] = Op::JumpUnless(*condition, ops.len());
+            }
+            Instruction::Call(_) => ops.push(Op::Step),
+        }
+    }
+}
+
+/// Runs compiled bytecode one command at a time, with the same step budget and the same
+/// results as the tree interpreter.
+pub struct Vm<'a> {
+    code: &'a Bytecode,
+    address: usize,
+    counters: Vec<u64>,
+    steps_left: u64,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(code: &'a Bytecode, budget: u64) -> Vm<'a> {
+        Vm {
+            code,
+            address: 0,
+            counters: vec![],
+            steps_left: budget,
+        }
+    }
+
+    /// How much of the budget is left.
+    pub fn steps_left(&self) -> u64 {
+        self.steps_left
+    }
+
+    /// The next command to execute, or `None` once the program has finished.
+    pub fn next_command(
+        &mut self,
+        sense: impl Fn(Sensor) -> bool,
+    ) -> Result<Option<Command>, BudgetExceeded> {
+        while let Some(op) = self.code.ops.get(self.address) {
+            self.address += 1;
+            match op {
+                Op::Next(start) => {
+                    if let Some(counter) = self.counters.last_mut() {
+                        *counter -= 1;
+                        if *counter > 0 {
+                            self.address = *start;
+                        } else {
+                            self.counters.pop();
+                        }
+                    }
+                    continue;
+                }
+                Op::Jump(address) => {
+                    self.address = *address;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if self.steps_left == 0 {
+                return Err(BudgetExceeded);
+            }
+            self.steps_left -= 1;
+
+            match op {
+                Op::Command(command) => return Ok(Some(*command)),
+                Op::Repeat(count) => self.counters.push(*count),
+                Op::JumpUnless(condition, address) => {
+                    if !condition.holds(&sense) {
+                        self.address = *address;
+                    }
+                }
+                Op::Step | Op::Next(_) | Op::Jump(_) => {}
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::{parse, Interpreter};
+
+    fn trace(next: &mut dyn FnMut() -> Result<Option<Command>, BudgetExceeded>) -> Vec<String> {
+        let mut trace = vec![];
+        loop {
+            match next() {
+                Ok(Some(command)) => trace.push(command.to_string()),
+                Ok(None) => return trace,
+                Err(e) => {
+                    trace.push(e.to_string());
+                    return trace;
+                }
+            }
+        }
+    }
+
+    /// Runs the program under both the interpreter and the VM, with a sensor that reads
+    /// blocked every third check and edge every fifth, and compares what they do.
+    fn assert_matches_interpreter(source: &str, budget: u64) {
+        let program = parse(source).expect("should parse");
+        let code = compile(&program);
+
+        let checks = std::cell::Cell::new(0);
+        let sense = |sensor: Sensor| {
+            checks.set(checks.get() + 1);
+            match sensor {
+                Sensor::Blocked => checks.get() % 3 == 0,
+                Sensor::Edge => checks.get() % 5 == 0,
+            }
+        };
+
+        let mut interpreter = Interpreter::new(&program, budget);
+        let expected = trace(&mut || interpreter.next_command(sense));
+        checks.set(0);
+        let mut vm = Vm::new(&code, budget);
+        let actual = trace(&mut || vm.next_command(sense));
+
+        assert_eq!(expected, actual, "program {}", source);
+    }
+
+    #[test]
+    fn vm_matches_interpreter() {
+        let programs = [
+            "MRLMB",
+            "M3(RM)2",
+            "((M)2R)3",
+            "()5 M",
+            "if blocked { R } else { M2 } L",
+            "while not edge { if blocked { R } else { M } }",
+            "(while not edge { M } R)4",
+            "(if edge { L })3 P",
+        ];
+        for source in programs.iter() {
+            for budget in [0, 1, 3, 7, 20, 1000].iter() {
+                assert_matches_interpreter(source, *budget);
+            }
+        }
+    }
+
+    #[test]
+    fn disassemble_loop() {
+        let code = compile(&parse("(MR)2 if blocked { L }").expect("should parse"));
+
+        assert_eq!(
+            "0000 REPEAT 2\n\
+             0001 COMMAND M\n\
+             0002 COMMAND R\n\
+             0003 NEXT 0001\n\
+             0004 JUMP_UNLESS blocked 0007\n\
+             0005 COMMAND L\n\
+             0006 JUMP 0007",
+            code.disassemble()
+        );
+    }
+}
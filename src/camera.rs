@@ -0,0 +1,211 @@
+use crate::rover::{Coordinates, Vehicle};
+use crate::terrain::Terrain;
+
+#[derive(Hash, Eq, PartialEq, PartialOrd, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FieldOfView {
+    Radius,
+    Cone,
+}
+
+/// The longest range a camera can have. Longer ranges are clamped to it, which keeps
+/// squared distances within `i64`.
+pub const MAX_RANGE: u64 = i32::MAX as u64;
+
+#[derive(Hash, Eq, PartialEq, PartialOrd, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Camera {
+    range: u64,
+    field_of_view: FieldOfView,
+}
+
+impl Camera {
+    pub fn new(range: u64, field_of_view: FieldOfView) -> Camera {
+        Camera {
+            range: range.min(MAX_RANGE),
+            field_of_view,
+        }
+    }
+
+    /// The same camera with its range clamped to `range`. A camera that sees further than
+    /// the plateau reaches images nothing more, so clamping to the plateau's size bounds
+    /// the cells scanned on every survey.
+    pub fn within(self, range: u64) -> Camera {
+        Camera::new(self.range.min(range), self.field_of_view)
+    }
+
+    pub fn range(&self) -> u64 {
+        self.range
+    }
+
+    pub fn field_of_view(&self) -> FieldOfView {
+        self.field_of_view
+    }
+
+    /// Cells the camera can see from the rover's pose. A cell is hidden when a rover or
+    /// obstacle sits on the line of sight between it and the camera, although the
+    /// blocking cell itself is still imaged.
+    pub fn imaged_cells<V: Vehicle, T: Terrain>(
+        &self,
+        vehicle: &V,
+        terrain: &T,
+    ) -> Vec<Coordinates> {
+        let origin = vehicle.coordinates();
+        let (facing_x, facing_y) = vehicle.bearing().direction();
+        let range = self.range as i64;
+
+        let mut cells = vec![];
+        for dy in -range..=range {
+            for dx in -range..=range {
+                if dx * dx + dy * dy > range * range {
+                    continue;
+                }
+                if self.field_of_view == FieldOfView::Cone {
+                    let forward = dx * facing_x + dy * facing_y;
+                    let lateral = (dx * facing_y - dy * facing_x).abs();
+                    if forward < lateral {
+                        continue;
+                    }
+                }
+                let target = match offset(origin, dx, dy) {
+                    Some(target) if terrain.is_inbounds(&target) => target,
+                    _ => continue,
+                };
+                if is_in_sight(origin, &target, terrain) {
+                    cells.push(target);
+                }
+            }
+        }
+        cells
+    }
+}
+
+fn offset(origin: &Coordinates, dx: i64, dy: i64) -> Option<Coordinates> {
+    let x_coordinate = (origin.x_coordinate as i64).checked_add(dx)?;
+    let y_coordinate = (origin.y_coordinate as i64).checked_add(dy)?;
+    if x_coordinate < 0 || y_coordinate < 0 {
+        return None;
+    }
+    Some(Coordinates::new(x_coordinate as u64, y_coordinate as u64))
+}
+
+/// Walks a Bresenham line between the two cells, checking every cell strictly between them.
+fn is_in_sight<T: Terrain>(from: &Coordinates, to: &Coordinates, terrain: &T) -> bool {
+    if from == to {
+        return true;
+    }
+    let (mut x, mut y) = (from.x_coordinate as i64, from.y_coordinate as i64);
+    let (to_x, to_y) = (to.x_coordinate as i64, to.y_coordinate as i64);
+    let dx = (to_x - x).abs();
+    let dy = -(to_y - y).abs();
+    let step_x = if x < to_x { 1 } else { -1 };
+    let step_y = if y < to_y { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    loop {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        if x == to_x && y == to_y {
+            return true;
+        }
+        if terrain.is_occupied(&Coordinates::new(x as u64, y as u64)) {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plateau::Plateau;
+    use crate::rover::{Bearing, Rover};
+
+    #[test]
+    fn radius_images_surrounding_cells() {
+        let plateau = Plateau::new(5, 5);
+        let rover = Rover::new(2, 2, Bearing::North);
+
+        let cells = Camera::new(1, FieldOfView::Radius).imaged_cells(&rover, &plateau);
+
+        assert_eq!(
+            vec![
+                Coordinates::new(2, 1),
+                Coordinates::new(1, 2),
+                Coordinates::new(2, 2),
+                Coordinates::new(3, 2),
+                Coordinates::new(2, 3),
+            ],
+            cells
+        );
+    }
+
+    #[test]
+    fn cone_images_ahead_of_bearing() {
+        let plateau = Plateau::new(5, 5);
+        let rover = Rover::new(0, 2, Bearing::East);
+
+        let cells = Camera::new(1, FieldOfView::Cone).imaged_cells(&rover, &plateau);
+
+        assert_eq!(vec![Coordinates::new(0, 2), Coordinates::new(1, 2)], cells);
+    }
+
+    #[test]
+    fn cone_is_clipped_to_plateau() {
+        let plateau = Plateau::new(5, 5);
+        let rover = Rover::new(0, 0, Bearing::South);
+
+        let cells = Camera::new(3, FieldOfView::Cone).imaged_cells(&rover, &plateau);
+
+        assert_eq!(vec![Coordinates::new(0, 0)], cells);
+    }
+
+    #[test]
+    fn obstacles_occlude_cells_behind_them() {
+        let mut plateau = Plateau::new(5, 5);
+        let rover = Rover::new(0, 0, Bearing::North);
+
+        if let Err(e) = plateau.place_obstacle(Coordinates::new(0, 1)) {
+            panic!("should be able to place obstacle : {}", e)
+        };
+
+        let cells = Camera::new(3, FieldOfView::Cone).imaged_cells(&rover, &plateau);
+
+        assert!(cells.contains(&Coordinates::new(0, 1)));
+        assert!(!cells.contains(&Coordinates::new(0, 2)));
+        assert!(!cells.contains(&Coordinates::new(0, 3)));
+    }
+
+    #[test]
+    fn rovers_occlude_cells_behind_them() {
+        let mut plateau = Plateau::new(5, 5);
+        let rover = Rover::new(0, 0, Bearing::East);
+
+        if let Err(e) = plateau.drop_rover(Coordinates::new(1, 0)) {
+            panic!("should be able to drop rover : {}", e)
+        };
+
+        let cells = Camera::new(2, FieldOfView::Radius).imaged_cells(&rover, &plateau);
+
+        assert!(cells.contains(&Coordinates::new(1, 0)));
+        assert!(!cells.contains(&Coordinates::new(2, 0)));
+    }
+
+    #[test]
+    fn clamp_long_ranges() {
+        let plateau = Plateau::new(2, 2);
+        let rover = Rover::new(0, 0, Bearing::North);
+
+        let camera = Camera::new(u64::MAX, FieldOfView::Radius);
+
+        assert_eq!(MAX_RANGE, camera.range());
+        assert_eq!(4, camera.within(4).range());
+        assert_eq!(9, camera.within(4).imaged_cells(&rover, &plateau).len());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::rover::Coordinates;
+use crate::terrain::Terrain;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    visited: HashMap<usize, HashSet<Coordinates>>,
+    imaged: HashMap<usize, HashSet<Coordinates>>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    pub fn visit(&mut self, rover: usize, coordinates: Coordinates) {
+        self.visited.entry(rover).or_default().insert(coordinates);
+    }
+
+    pub fn image(&mut self, rover: usize, coordinates: Coordinates) {
+        self.imaged.entry(rover).or_default().insert(coordinates);
+    }
+
+    pub fn visited_by(&self, rover: usize) -> Vec<Coordinates> {
+        sorted(self.visited.get(&rover).into_iter().flatten().cloned())
+    }
+
+    pub fn imaged_by(&self, rover: usize) -> Vec<Coordinates> {
+        sorted(self.imaged.get(&rover).into_iter().flatten().cloned())
+    }
+
+    pub fn covered_by(&self, rover: usize) -> Vec<Coordinates> {
+        sorted(self.covered_set(rover).into_iter())
+    }
+
+    fn covered_set(&self, rover: usize) -> HashSet<Coordinates> {
+        self.visited
+            .get(&rover)
+            .into_iter()
+            .chain(self.imaged.get(&rover))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    pub fn report<T: Terrain>(&self, terrain: &T) -> CoverageReport {
+        let mut rovers: HashSet<usize> = self.visited.keys().cloned().collect();
+        rovers.extend(self.imaged.keys());
+
+        let mut covered_by: HashMap<Coordinates, usize> = HashMap::new();
+        for rover in rovers {
+            for coordinates in self.covered_set(rover) {
+                *covered_by.entry(coordinates).or_default() += 1;
+            }
+        }
+
+        let mut covered_cells = 0;
+        let mut uncovered = vec![];
+        let mut overlap = vec![];
+        for coordinates in terrain.cells() {
+            match covered_by.get(&coordinates) {
+                None => uncovered.push(coordinates),
+                Some(count) => {
+                    covered_cells += 1;
+                    if *count > 1 {
+                        overlap.push(coordinates);
+                    }
+                }
+            }
+        }
+
+        CoverageReport {
+            total_cells: covered_cells + uncovered.len() as u64,
+            covered_cells,
+            uncovered,
+            overlap,
+        }
+    }
+}
+
+/// Orders cells bottom row first, left to right, so reports are stable between runs.
+fn sorted(cells: impl Iterator<Item = Coordinates>) -> Vec<Coordinates> {
+    let mut cells: Vec<Coordinates> = cells.collect();
+    cells.sort_by_key(|c| (c.y_coordinate, c.x_coordinate));
+    cells
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageReport {
+    pub total_cells: u64,
+    pub covered_cells: u64,
+    pub uncovered: Vec<Coordinates>,
+    pub overlap: Vec<Coordinates>,
+}
+
+impl CoverageReport {
+    pub fn percentage(&self) -> f64 {
+        if self.total_cells == 0 {
+            return 0.0;
+        }
+        self.covered_cells as f64 / self.total_cells as f64 * 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plateau::Plateau;
+
+    #[test]
+    fn report_uncovered_cells() {
+        let plateau = Plateau::new(1, 1);
+        let mut coverage = Coverage::new();
+
+        coverage.visit(0, Coordinates::new(0, 0));
+        coverage.visit(0, Coordinates::new(0, 1));
+
+        let report = coverage.report(&plateau);
+
+        assert_eq!(4, report.total_cells);
+        assert_eq!(2, report.covered_cells);
+        assert_eq!(50.0, report.percentage());
+        assert_eq!(
+            vec![Coordinates::new(1, 0), Coordinates::new(1, 1)],
+            report.uncovered
+        );
+        assert!(report.overlap.is_empty());
+    }
+
+    #[test]
+    fn report_overlap_between_rovers() {
+        let plateau = Plateau::new(1, 1);
+        let mut coverage = Coverage::new();
+
+        coverage.visit(0, Coordinates::new(0, 0));
+        coverage.visit(0, Coordinates::new(1, 0));
+        coverage.visit(1, Coordinates::new(1, 0));
+        coverage.image(1, Coordinates::new(1, 1));
+
+        let report = coverage.report(&plateau);
+
+        assert_eq!(3, report.covered_cells);
+        assert_eq!(vec![Coordinates::new(1, 0)], report.overlap);
+        assert_eq!(
+            vec![Coordinates::new(1, 0), Coordinates::new(1, 1)],
+            coverage.covered_by(1)
+        );
+    }
+
+    #[test]
+    fn visiting_twice_is_not_overlap() {
+        let plateau = Plateau::new(0, 0);
+        let mut coverage = Coverage::new();
+
+        coverage.visit(0, Coordinates::new(0, 0));
+        coverage.image(0, Coordinates::new(0, 0));
+
+        let report = coverage.report(&plateau);
+
+        assert_eq!(100.0, report.percentage());
+        assert!(report.overlap.is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+use crate::plateau::{CollisionError, DropError, Plateau};
+use crate::rover::{Bearing, Compass, Coordinates, Vehicle};
+use crate::terrain::Terrain;
+use crate::{Command, Config, Deployment};
+use core::fmt;
+use std::error::Error;
+
+/// The six bearings available on a hex grid, clockwise from north. Hexes are flat topped,
+/// so there is no due east or west.
+pub const BEARINGS: [Bearing; 6] = [
+    Bearing::North,
+    Bearing::NorthEast,
+    Bearing::SouthEast,
+    Bearing::South,
+    Bearing::SouthWest,
+    Bearing::NorthWest,
+];
+
+/// Axial step taken when moving forward, as (q, r), or `None` for east and west.
+pub fn direction(bearing: Bearing) -> Option<(i64, i64)> {
+    match bearing {
+        Bearing::North => Some((0, 1)),
+        Bearing::NorthEast => Some((1, 0)),
+        Bearing::SouthEast => Some((1, -1)),
+        Bearing::South => Some((0, -1)),
+        Bearing::SouthWest => Some((-1, 0)),
+        Bearing::NorthWest => Some((-1, 1)),
+        Bearing::East | Bearing::West => None,
+    }
+}
+
+/// A rover on a hex grid. Its coordinates are axial, with `x_coordinate` as q and
+/// `y_coordinate` as r, and `L`/`R` turn it 60 degrees.
+#[derive(Hash, Eq, PartialEq, Debug)]
+pub struct HexRover {
+    bearing: Bearing,
+    coordinates: Coordinates,
+}
+
+impl HexRover {
+    pub fn new(q: u64, r: u64, bearing: Bearing) -> Result<HexRover, String> {
+        if direction(bearing).is_none() {
+            return Err(format!("{} is not a hex bearing", bearing));
+        }
+        Ok(HexRover {
+            bearing,
+            coordinates: Coordinates::new(q, r),
+        })
+    }
+
+    /// Where moving forward would take the rover, or `None` if it would step below zero or
+    /// past `u64::MAX`.
+    pub fn planned_move(&self) -> Option<Coordinates> {
+        let (dq, dr) = self.step(1);
+        self.coordinates.step(dq, dr)
+    }
+
+    pub fn planned_move_backward(&self) -> Option<Coordinates> {
+        let (dq, dr) = self.step(-1);
+        self.coordinates.step(dq, dr)
+    }
+
+    fn step(&self, sign: i64) -> (i64, i64) {
+        let (dq, dr) = direction(self.bearing).unwrap_or((0, 0));
+        (sign * dq, sign * dr)
+    }
+
+    pub fn coordinates(&self) -> &Coordinates {
+        &self.coordinates
+    }
+
+    pub fn bearing(&self) -> Bearing {
+        self.bearing
+    }
+
+    pub fn execute_command(&mut self, command: &Command) {
+        match command {
+            Command::RightTurn => self.turn(1),
+            Command::LeftTurn => self.turn(-1),
+            Command::MoveForward => {
+                self.coordinates = self.planned_move().unwrap_or(self.coordinates)
+            }
+            Command::MoveBackward => {
+                self.coordinates = self.planned_move_backward().unwrap_or(self.coordinates)
+            }
+            Command::HalfRightTurn | Command::HalfLeftTurn | Command::TakePhoto => {}
+        }
+    }
+
+    fn turn(&mut self, sixths: i64) {
+        let index = BEARINGS
+            .iter()
+            .position(|b| *b == self.bearing)
+            .unwrap_or(0) as i64;
+        self.bearing = BEARINGS[(index + sixths).rem_euclid(BEARINGS.len() as i64) as usize];
+    }
+}
+
+impl Vehicle for HexRover {
+    const HEADINGS: u64 = 6;
+
+    fn land(q: u64, r: u64, bearing: Bearing) -> Result<HexRover, String> {
+        HexRover::new(q, r, bearing)
+    }
+
+    fn check_bearing(bearing: Bearing, _compass: Compass) -> Result<(), String> {
+        match direction(bearing) {
+            Some(_) => Ok(()),
+            None => Err(format!("{} is not a hex bearing", bearing)),
+        }
+    }
+
+    fn check_command(command: &Command, _compass: Compass) -> Result<(), String> {
+        match command {
+            Command::HalfLeftTurn | Command::HalfRightTurn => {
+                Err(String::from("half turns are not possible on a hex grid"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn turn_steps(command: &Command) -> Option<u64> {
+        match command {
+            Command::RightTurn => Some(1),
+            Command::LeftTurn => Some(5),
+            _ => None,
+        }
+    }
+
+    fn coordinates(&self) -> &Coordinates {
+        HexRover::coordinates(self)
+    }
+
+    fn bearing(&self) -> Bearing {
+        HexRover::bearing(self)
+    }
+
+    fn planned_step(&self, command: &Command) -> Option<(i64, i64)> {
+        match command {
+            Command::MoveForward => Some(self.step(1)),
+            Command::MoveBackward => Some(self.step(-1)),
+            _ => None,
+        }
+    }
+
+    fn execute_command(&mut self, command: &Command) {
+        HexRover::execute_command(self, command)
+    }
+
+    fn move_to(&mut self, coordinates: Coordinates) {
+        self.coordinates = coordinates;
+    }
+
+    fn face(&mut self, bearing: Bearing) {
+        self.bearing = bearing;
+    }
+}
+
+impl fmt::Display for HexRover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.coordinates.x_coordinate, self.coordinates.y_coordinate, self.bearing
+        )
+    }
+}
+
+/// A plateau tiled with hexes. Axial coordinates run from zero to the maximum on both
+/// axes, giving a rhombus shaped plateau with the same bounds checks as a square one.
+#[derive(Clone)]
+pub struct HexPlateau {
+    plateau: Plateau,
+}
+
+impl HexPlateau {
+    pub fn new(max_q: u64, max_r: u64) -> HexPlateau {
+        HexPlateau {
+            plateau: Plateau::new(max_q, max_r),
+        }
+    }
+
+    pub fn list_rovers(&self) -> Vec<Coordinates> {
+        self.plateau.list_rovers()
+    }
+
+    /// Draws the plateau with flat topped hexes, so each column sits half a row above the
+    /// one to its left. Rovers are `R`, obstacles `#` and empty hexes `.`.
+    pub fn render(&self) -> String {
+        let max_q = self.plateau.max_x_coordinate();
+        let max_r = self.plateau.max_y_coordinate();
+        let rovers = self.plateau.list_rovers();
+        let obstacles = self.plateau.list_obstacles();
+
+        let mut lines = vec![];
+        for row in (0..=2 * max_r + max_q).rev() {
+            let mut line = String::new();
+            for q in 0..=max_q {
+                let doubled_r = row.checked_sub(q).filter(|r| r % 2 == 0);
+                let cell = match doubled_r.map(|r| Coordinates::new(q, r / 2)) {
+                    Some(c) if c.y_coordinate > max_r => ' ',
+                    Some(c) if rovers.contains(&c) => 'R',
+                    Some(c) if obstacles.contains(&c) => '#',
+                    Some(_) => '.',
+                    None => ' ',
+                };
+                line.push(cell);
+                line.push(' ');
+            }
+            lines.push(line.trim_end().to_string());
+        }
+        lines.join("\n")
+    }
+}
+
+impl Terrain for HexPlateau {
+    fn is_inbounds(&self, coordinates: &Coordinates) -> bool {
+        self.plateau.is_inbounds(coordinates)
+    }
+
+    fn is_occupied(&self, coordinates: &Coordinates) -> bool {
+        self.plateau.is_occupied(coordinates)
+    }
+
+    fn has_obstacle(&self, coordinates: &Coordinates) -> bool {
+        Terrain::has_obstacle(&self.plateau, coordinates)
+    }
+
+    fn cells(&self) -> Vec<Coordinates> {
+        Terrain::cells(&self.plateau)
+    }
+
+    fn drop_rover(&mut self, coordinates: Coordinates) -> Result<(), Box<dyn Error>> {
+        Terrain::drop_rover(&mut self.plateau, coordinates)
+    }
+
+    fn place_obstacle(&mut self, coordinates: Coordinates) -> Result<(), DropError> {
+        self.plateau.place_obstacle(coordinates)
+    }
+
+    fn can_rover_move(&self, coordinates: &Coordinates) -> Result<(), CollisionError> {
+        self.plateau.can_rover_move(coordinates)
+    }
+
+    fn update_rover_position(
+        &mut self,
+        old_coordinates: &Coordinates,
+        new_coordinates: &Coordinates,
+    ) -> Result<(), Box<dyn Error>> {
+        self.plateau
+            .update_rover_position(old_coordinates, new_coordinates)
+    }
+
+    fn render(&self) -> String {
+        HexPlateau::render(self)
+    }
+
+    /// Neighbouring hexes always share an edge, so there is nothing to squeeze between.
+    fn can_rover_squeeze(
+        &self,
+        _from: &Coordinates,
+        _to: &Coordinates,
+    ) -> Result<(), CollisionError> {
+        Ok(())
+    }
+}
+
+/// Runs the mission on a hex plateau. Input is read exactly as for a square plateau,
+/// with starting positions taken as axial coordinates.
+pub fn deploy(config: Config) -> Result<Deployment<HexPlateau, HexRover>, Box<dyn Error>> {
+    let plateau = HexPlateau::new(config.max_x_grid(), config.max_y_grid());
+    crate::drive(plateau, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_rover_needs_hex_bearing() {
+        assert!(HexRover::new(0, 0, Bearing::East).is_err());
+    }
+
+    #[test]
+    fn turn_right_through_all_bearings() {
+        let mut rover = HexRover::new(0, 0, Bearing::North).expect("should create rover");
+
+        for expected in BEARINGS.iter().cycle().skip(1).take(6) {
+            rover.execute_command(&Command::RightTurn);
+            assert_eq!(*expected, rover.bearing());
+        }
+    }
+
+    #[test]
+    fn turn_left_from_north() {
+        let mut rover = HexRover::new(0, 0, Bearing::North).expect("should create rover");
+
+        rover.execute_command(&Command::LeftTurn);
+
+        assert_eq!(Bearing::NorthWest, rover.bearing());
+    }
+
+    #[test]
+    fn move_south_east() {
+        let mut rover = HexRover::new(1, 1, Bearing::SouthEast).expect("should create rover");
+
+        rover.execute_command(&Command::MoveForward);
+
+        assert_eq!(&Coordinates::new(2, 0), rover.coordinates());
+    }
+
+    #[test]
+    fn six_moves_around_a_hex_return_home() {
+        let mut rover = HexRover::new(1, 1, Bearing::North).expect("should create rover");
+
+        for _ in 0..6 {
+            rover.execute_command(&Command::MoveForward);
+            rover.execute_command(&Command::RightTurn);
+        }
+
+        assert_eq!(&Coordinates::new(1, 1), rover.coordinates());
+        assert_eq!(Bearing::North, rover.bearing());
+    }
+
+    #[test]
+    fn render_hex_plateau() {
+        let mut plateau = HexPlateau::new(2, 1);
+
+        if let Err(e) = plateau.drop_rover(Coordinates::new(1, 0)) {
+            panic!("should be able to drop rover : {}", e)
+        };
+        if let Err(e) = plateau.place_obstacle(Coordinates::new(0, 1)) {
+            panic!("should be able to place obstacle : {}", e)
+        };
+
+        assert_eq!("    .\n  .\n#   .\n  R\n.", plateau.render());
+    }
+}
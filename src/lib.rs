@@ -1,52 +1,527 @@
 use core::fmt;
 use std::error::Error;
+use std::io;
+use std::str::FromStr;
 
+pub mod analysis;
+pub mod bytecode;
+pub mod camera;
+pub mod coverage;
+pub mod hex;
+pub mod lint;
+pub mod mission;
+pub mod optimise;
+pub mod photo;
 pub mod plateau;
+pub mod pose;
+pub mod program;
+pub mod repl;
 pub mod rover;
+pub mod stream;
+pub mod terrain;
+pub mod trace;
 
+use rover::Vehicle;
+use terrain::Terrain;
+
+/// Deploys the rovers on a square plateau and returns where they finish. Nothing else
+/// about the mission is recorded, so runs of commands that provably stay in bounds and
+/// clear of everything else on the plateau are fast-forwarded instead of stepped.
 pub fn deploy_rovers(config: Config) -> Result<Vec<rover::Rover>, Box<dyn Error>> {
-    let mut plateau = plateau::Plateau::new(config.max_x_grid, config.max_y_grid);
+    Ok(deploy(config.without_coverage())?.rovers)
+}
+
+/// Runs the mission on any terrain like `drive`, except that once the chosen rover has
+/// finished its program it carries on with commands read from `log` as they arrive.
+/// Replayed commands do not count against the instruction budget, and only the command
+/// being executed is held in memory, so recorded logs of any size replay in constant
+/// memory. Nothing but where the rovers finish is recorded.
+pub fn replay<T: Terrain, V: Vehicle, R: io::Read>(
+    terrain: T,
+    config: Config,
+    rover: usize,
+    log: R,
+) -> Result<Vec<V>, Box<dyn Error>> {
+    if rover >= config.instructions.len() {
+        let details = format!("no rover {} to replay commands for", rover);
+        return Err(Box::new(ParseError::new(&details)));
+    }
+    let mut commands = stream::CommandStream::new(log);
+    let deployment = drive_with(
+        terrain,
+        config.without_coverage(),
+        false,
+        Some((rover, &mut commands)),
+    )?;
+    Ok(deployment.rovers)
+}
+
+type Replay<'a> = (
+    usize,
+    &'a mut dyn Iterator<Item = Result<Command, ParseError>>,
+);
+
+pub fn deploy(config: Config) -> Result<Deployment, Box<dyn Error>> {
+    let plateau = plateau::Plateau::new(config.max_x_grid, config.max_y_grid);
+    drive(plateau, config)
+}
+
+/// Runs the mission on any terrain with any kind of vehicle. Rovers land and move one at
+/// a time, each finishing its commands before the next lands.
+pub fn drive<T: Terrain, V: Vehicle>(
+    terrain: T,
+    config: Config,
+) -> Result<Deployment<T, V>, Box<dyn Error>> {
+    drive_with(terrain, config, false, None)
+}
+
+/// Runs the mission like `drive`, except that a rover which collides, is lost off the
+/// terrain or runs out of budget stops where it is and the next rover carries on.
+/// `Deployment::outcomes` reports what happened to each rover. Missions the vehicles
+/// cannot follow at all still fail before anything lands.
+pub fn drive_tolerant<T: Terrain, V: Vehicle>(
+    terrain: T,
+    config: Config,
+) -> Result<Deployment<T, V>, Box<dyn Error>> {
+    drive_with(terrain, config, true, None)
+}
+
+fn drive_with<T: Terrain, V: Vehicle>(
+    terrain: T,
+    config: Config,
+    tolerant: bool,
+    mut replay: Option<Replay>,
+) -> Result<Deployment<T, V>, Box<dyn Error>> {
+    config.check::<V>()?;
+
+    let mut driver = Driver {
+        terrain,
+        config: &config,
+        coverage: coverage::Coverage::new(),
+        photos: photo::PhotoLog::new(),
+        trace: trace::Trace::new(),
+        tick: 0,
+    };
+    for obstacle in &config.obstacles {
+        driver.terrain.place_obstacle(*obstacle)?;
+    }
 
     let mut rovers = vec![];
+    let mut outcomes = vec![];
 
-    for instruction in config.instructions.iter() {
-        let mut rover = rover::Rover::new(
+    for (id, instruction) in config.instructions.iter().enumerate() {
+        let mut rover = V::land(
             instruction.starting_x,
             instruction.starting_y,
             instruction.bearing,
-        );
-        plateau.drop_rover(rover::Coordinates::new(
-            instruction.starting_x,
-            instruction.starting_y,
-        ))?;
-
-        for command in &instruction.commands {
-            match command {
-                Command::LeftTurn => rover.execute_command(command),
-                Command::RightTurn => rover.execute_command(command),
-                Command::MoveForward => {
-                    let planned_coordinates = rover.planned_move();
-                    plateau.can_rover_move(&planned_coordinates)?;
-                    plateau.update_rover_position(rover.coordinates(), &planned_coordinates)?;
-                    rover.execute_command(command);
+        )?;
+        if let Some(camera) = instruction.camera {
+            rover.fit_camera(camera.within(config.reach()))?;
+        }
+
+        let mut executed = 0;
+        let commands = match replay {
+            Some((rover, _)) if rover == id => replay.take().map(|(_, commands)| commands),
+            _ => None,
+        };
+        let outcome = match driver.run(
+            id,
+            &mut rover,
+            &instruction.program,
+            commands,
+            &mut executed,
+        ) {
+            Ok(()) => Outcome::new(Status::Ok, executed, None),
+            Err(e) if tolerant => Outcome::new(Status::of(e.as_ref()), executed, Some(e)),
+            Err(e) => return Err(e),
+        };
+        rovers.push(rover);
+        outcomes.push(outcome);
+    }
+
+    Ok(Deployment {
+        plateau: driver.terrain,
+        rovers,
+        coverage: driver.coverage,
+        photos: driver.photos,
+        trace: driver.trace,
+        outcomes,
+    })
+}
+
+/// Everything a mission records while its rovers are driven.
+struct Driver<'a, T> {
+    terrain: T,
+    config: &'a Config,
+    coverage: coverage::Coverage,
+    photos: photo::PhotoLog,
+    trace: trace::Trace,
+    tick: u64,
+}
+
+impl<'a, T: Terrain> Driver<'a, T> {
+    /// Lands the rover and runs its program, then any replayed commands. Every command
+    /// carried out is counted in `executed`, including those before the rover stopped.
+    fn run<V: Vehicle>(
+        &mut self,
+        id: usize,
+        rover: &mut V,
+        program: &[program::Instruction],
+        replay: Option<&mut dyn Iterator<Item = Result<Command, ParseError>>>,
+        executed: &mut u64,
+    ) -> Result<(), Box<dyn Error>> {
+        self.terrain.drop_rover(*rover.coordinates())?;
+        self.visit(id, &*rover);
+
+        let mut budget = self.config.budget(program);
+        // Fast-forwarding skips the cells in between, so it is only done when nothing
+        // records them.
+        if self.config.trace || self.config.coverage || rover.camera().is_some() {
+            self.step_through(id, rover, program, &mut budget, executed)?;
+        } else {
+            for segment in segments::<V>(program) {
+                self.fast_forward(id, rover, segment, &mut budget, executed)?;
+            }
+        }
+
+        if let Some(commands) = replay {
+            for command in commands {
+                let command = command?;
+                V::check_command(&command, self.config.compass).map_err(|e| ParseError::new(&e))?;
+                self.carry_out(id, rover, &command)?;
+                *executed += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Jumps the rover to the end of the segment if it provably stays in bounds and clear
+    /// of everything else on the terrain, tries each half in turn if not, and steps
+    /// through what is left.
+    fn fast_forward<V: Vehicle>(
+        &mut self,
+        id: usize,
+        rover: &mut V,
+        segment: &[program::Instruction],
+        budget: &mut u64,
+        executed: &mut u64,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(transform) = straight::<V>(segment) {
+            let clear = match transform.bounds(*rover.coordinates(), rover.bearing()) {
+                Some((min, max)) => self.terrain.is_clear(&min, &max, rover.coordinates()),
+                None => false,
+            };
+            let end = transform
+                .apply(*rover.coordinates(), rover.bearing())
+                .filter(|_| transform.steps() <= *budget && clear);
+            if let Some((coordinates, bearing)) = end {
+                if coordinates != *rover.coordinates() {
+                    self.terrain
+                        .update_rover_position(rover.coordinates(), &coordinates)?;
+                    rover.move_to(coordinates);
                 }
+                rover.face(bearing);
+                *budget -= transform.steps();
+                let commands = program::executed(segment);
+                *executed += commands;
+                self.tick += commands;
+                return Ok(());
+            }
+            if segment.len() > 1 {
+                let (first, second) = segment.split_at(segment.len() / 2);
+                self.fast_forward(id, rover, first, budget, executed)?;
+                return self.fast_forward(id, rover, second, budget, executed);
             }
         }
+        self.step_through(id, rover, segment, budget, executed)
+    }
 
-        rovers.push(rover);
+    /// Runs a program one command at a time.
+    fn step_through<V: Vehicle>(
+        &mut self,
+        id: usize,
+        rover: &mut V,
+        program: &[program::Instruction],
+        budget: &mut u64,
+        executed: &mut u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let code = bytecode::compile(program);
+        let mut vm = bytecode::Vm::new(&code, *budget);
+        while let Some(command) = vm.next_command(|sensor| sense(sensor, &*rover, &self.terrain))? {
+            self.carry_out(id, rover, &command)?;
+            *executed += 1;
+        }
+        *budget = vm.steps_left();
+        Ok(())
+    }
+
+    /// Carries out one command and records what it did.
+    fn carry_out<V: Vehicle>(
+        &mut self,
+        id: usize,
+        rover: &mut V,
+        command: &Command,
+    ) -> Result<(), Box<dyn Error>> {
+        if *command == Command::TakePhoto {
+            let imaged = self.survey(id, &*rover);
+            self.photos.record(photo::PhotoRecord {
+                rover: id,
+                tick: self.tick,
+                coordinates: *rover.coordinates(),
+                bearing: rover.bearing(),
+                imaged,
+            });
+        } else {
+            match step(&mut self.terrain, rover, command, self.config.compass) {
+                Ok(true) => self.visit(id, &*rover),
+                Ok(false) => {}
+                Err(e) => {
+                    if self.config.trace {
+                        let outcome = Status::of(e.as_ref());
+                        let record =
+                            trace_record(self.config, id, self.tick, *command, &*rover, outcome);
+                        self.trace.record(record);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        if self.config.trace {
+            let record = trace_record(self.config, id, self.tick, *command, &*rover, Status::Ok);
+            self.trace.record(record);
+        }
+        self.tick += 1;
+        Ok(())
     }
 
-    Ok(rovers)
+    /// Records the cell the rover stands on and what its camera sees from there, unless
+    /// the mission runs without coverage.
+    fn visit<V: Vehicle>(&mut self, id: usize, rover: &V) {
+        if self.config.coverage {
+            self.coverage.visit(id, *rover.coordinates());
+            self.survey(id, rover);
+        }
+    }
+
+    /// Returns the cells the rover's camera can see, if it has one, recording them as
+    /// imaged unless the mission runs without coverage.
+    fn survey<V: Vehicle>(&mut self, id: usize, rover: &V) -> Vec<rover::Coordinates> {
+        let imaged = match rover.camera() {
+            Some(camera) => camera.imaged_cells(rover, &self.terrain),
+            None => vec![],
+        };
+        if self.config.coverage {
+            for coordinates in &imaged {
+                self.coverage.image(id, *coordinates);
+            }
+        }
+        imaged
+    }
+}
+
+/// The transform of a run of instructions that the vehicle can jump over, or `None` if
+/// it has to be stepped. Photos are always stepped so that each one is recorded.
+fn straight<V: Vehicle>(program: &[program::Instruction]) -> Option<pose::PoseTransform> {
+    if program::commands(program).contains(&&Command::TakePhoto) {
+        return None;
+    }
+    V::transform(program)
+}
+
+/// Splits a program into runs of instructions the vehicle can jump over, and the
+/// instructions between them that it cannot.
+fn segments<V: Vehicle>(program: &[program::Instruction]) -> Vec<&[program::Instruction]> {
+    let mut segments = vec![];
+    let mut start = 0;
+    for (index, instruction) in program.iter().enumerate() {
+        if straight::<V>(std::slice::from_ref(instruction)).is_none() {
+            if start < index {
+                segments.push(&program[start..index]);
+            }
+            segments.push(&program[index..=index]);
+            start = index + 1;
+        }
+    }
+    if start < program.len() {
+        segments.push(&program[start..]);
+    }
+    segments
+}
+
+fn trace_record<V: Vehicle>(
+    config: &Config,
+    id: usize,
+    tick: u64,
+    command: Command,
+    rover: &V,
+    outcome: Status,
+) -> trace::TraceRecord {
+    trace::TraceRecord {
+        tick,
+        rover: config.rover_name(id),
+        command,
+        coordinates: *rover.coordinates(),
+        bearing: rover.bearing(),
+        outcome,
+    }
+}
+
+/// Carries out a command other than taking a photo, checking any move against the terrain
+/// first. Returns whether the rover moved.
+fn step<T: Terrain, V: Vehicle>(
+    terrain: &mut T,
+    rover: &mut V,
+    command: &Command,
+    compass: rover::Compass,
+) -> Result<bool, Box<dyn Error>> {
+    let planned_coordinates = match rover.planned_step(command) {
+        Some(step) => terrain
+            .resolve(rover.coordinates(), step)
+            .ok_or(plateau::OutOfBounds)?,
+        None => {
+            rover.execute_command(command);
+            return Ok(false);
+        }
+    };
+    terrain.can_rover_move(&planned_coordinates)?;
+    if let rover::Compass::EightPoint {
+        allow_squeeze: false,
+    } = compass
+    {
+        terrain.can_rover_squeeze(rover.coordinates(), &planned_coordinates)?;
+    }
+    terrain.update_rover_position(rover.coordinates(), &planned_coordinates)?;
+    rover.move_to(planned_coordinates);
+    Ok(true)
+}
+
+/// Reads a sensor by looking at the cell the rover would move into next.
+fn sense<T: Terrain, V: Vehicle>(sensor: program::Sensor, rover: &V, terrain: &T) -> bool {
+    let step = match rover.planned_step(&Command::MoveForward) {
+        Some(step) => step,
+        None => return false,
+    };
+    // A step off the coordinate grid is past an edge, with nothing there to block it.
+    let ahead = terrain.resolve(rover.coordinates(), step);
+    match sensor {
+        program::Sensor::Blocked => ahead.is_some_and(|ahead| terrain.is_occupied(&ahead)),
+        program::Sensor::Edge => !ahead.is_some_and(|ahead| terrain.is_inbounds(&ahead)),
+    }
+}
+
+pub struct Deployment<T = plateau::Plateau, V = rover::Rover> {
+    plateau: T,
+    rovers: Vec<V>,
+    coverage: coverage::Coverage,
+    photos: photo::PhotoLog,
+    trace: trace::Trace,
+    outcomes: Vec<Outcome>,
+}
+
+impl<T: Terrain, V: Vehicle> Deployment<T, V> {
+    pub fn plateau(&self) -> &T {
+        &self.plateau
+    }
+
+    pub fn rovers(&self) -> &[V] {
+        &self.rovers
+    }
+
+    pub fn coverage(&self) -> &coverage::Coverage {
+        &self.coverage
+    }
+
+    pub fn photos(&self) -> &photo::PhotoLog {
+        &self.photos
+    }
+
+    pub fn coverage_report(&self) -> coverage::CoverageReport {
+        self.coverage.report(&self.plateau)
+    }
+
+    /// Every command carried out, if the mission asked for a trace with
+    /// `Config::with_trace`.
+    pub fn trace(&self) -> &trace::Trace {
+        &self.trace
+    }
+
+    /// What happened to each rover, in landing order.
+    pub fn outcomes(&self) -> &[Outcome] {
+        &self.outcomes
+    }
+}
+
+/// How a rover's program ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Ok,
+    /// Stopped by another rover or an obstacle.
+    Collided,
+    /// Stopped at the edge of the terrain.
+    Lost,
+    /// Stopped for running out of instruction budget.
+    Aborted,
+}
+
+impl Status {
+    /// Classifies the error that stopped a rover.
+    pub fn of(error: &(dyn Error + 'static)) -> Status {
+        if error.is::<plateau::OutOfBounds>() {
+            Status::Lost
+        } else if error.is::<plateau::CollisionError>() || error.is::<plateau::DropError>() {
+            Status::Collided
+        } else {
+            Status::Aborted
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            Status::Ok => "ok",
+            Status::Collided => "collided",
+            Status::Lost => "lost",
+            Status::Aborted => "aborted",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub status: Status,
+    /// How many commands the rover carried out before it finished or stopped.
+    pub commands: u64,
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    fn new(status: Status, commands: u64, error: Option<Box<dyn Error>>) -> Outcome {
+        Outcome {
+            status,
+            commands,
+            error: error.map(|e| e.to_string()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Config {
     max_x_grid: u64,
     max_y_grid: u64,
+    compass: rover::Compass,
+    budget: Option<u64>,
+    trace: bool,
+    coverage: bool,
+    obstacles: Vec<rover::Coordinates>,
     instructions: Vec<RoverInstructions>,
 }
 
 impl Config {
+    /// Reads a mission from the command line: the plateau size, then a starting pose and
+    /// a program for each rover. Anything wrong with the arguments is a `ParseError`.
     pub fn new(args: &[String]) -> Result<Config, Box<dyn Error>> {
         if args.len() < 7 {
             return Err(Box::new(ParseError::new(
@@ -57,50 +532,199 @@ impl Config {
         let mut args_iter = args.iter();
         args_iter.next();
 
-        let max_x_grid = args_iter
-            .next()
-            .ok_or("could not read first argument")?
-            .parse()?;
-
-        let max_y_grid = args_iter
-            .next()
-            .ok_or("could not read second argument")?
-            .parse()?;
+        let max_x_grid = argument(args_iter.next(), "first argument")?;
+        let max_y_grid = argument(args_iter.next(), "second argument")?;
 
         let mut instructions = vec![];
 
         for next_rover_args in args_iter.as_slice().chunks_exact(4) {
             let mut iter = next_rover_args.iter();
 
-            let starting_x = iter.next().ok_or("starting x not given")?.parse()?;
-            let starting_y = iter.next().ok_or("starting y not given")?.parse()?;
-            let bearing = iter.next().ok_or("no bearing given")?.parse()?;
-            let commands = iter
+            let starting_x = argument(iter.next(), "starting x")?;
+            let starting_y = argument(iter.next(), "starting y")?;
+            let bearing = argument(iter.next(), "bearing")?;
+            let source = iter
                 .next()
-                .ok_or("no commands given")?
-                .chars()
-                .map(Command::new)
-                .filter_map(Result::ok)
-                .collect();
+                .ok_or_else(|| ParseError::new("no commands given"))?;
+            let program = program::Macros::new().resolve(&program::parse(source)?)?;
             instructions.push(RoverInstructions::new(
-                starting_x, starting_y, bearing, commands,
+                starting_x, starting_y, bearing, program,
             ))
         }
 
-        Ok(Config {
+        Ok(Config::from_instructions(
+            max_x_grid,
+            max_y_grid,
+            instructions,
+        ))
+    }
+
+    fn from_instructions(
+        max_x_grid: u64,
+        max_y_grid: u64,
+        instructions: Vec<RoverInstructions>,
+    ) -> Config {
+        Config {
             max_x_grid,
             max_y_grid,
+            compass: rover::Compass::default(),
+            budget: None,
+            trace: false,
+            coverage: true,
+            obstacles: vec![],
             instructions,
-        })
+        }
+    }
+
+    pub fn with_compass(mut self, compass: rover::Compass) -> Config {
+        self.compass = compass;
+        self
+    }
+
+    /// Limits how many steps each rover's program may take, so programs that loop on
+    /// their sensors are guaranteed to finish. Without it, only programs with a `while`
+    /// loop are limited, to `program::MAX_COMMANDS` steps.
+    pub fn with_budget(mut self, budget: u64) -> Config {
+        self.budget = Some(budget);
+        self
     }
+
+    /// How many steps the rover's program may take.
+    fn budget(&self, program: &[program::Instruction]) -> u64 {
+        match self.budget {
+            Some(budget) => budget,
+            None if program::loops(program) => program::MAX_COMMANDS,
+            None => u64::MAX,
+        }
+    }
+
+    /// Records every command each rover carries out in `Deployment::trace`. Traces hold
+    /// one record per command, so they are off unless asked for.
+    pub fn with_trace(mut self) -> Config {
+        self.trace = true;
+        self
+    }
+
+    /// Leaves `Deployment::coverage` empty, so that runs of commands clear of everything
+    /// else on the terrain can be fast-forwarded rather than stepped cell by cell.
+    pub fn without_coverage(mut self) -> Config {
+        self.coverage = false;
+        self
+    }
+
+    pub fn with_obstacle(mut self, coordinates: rover::Coordinates) -> Config {
+        self.obstacles.push(coordinates);
+        self
+    }
+
+    /// The furthest apart two cells of the plateau can be, which no camera needs to see
+    /// beyond.
+    fn reach(&self) -> u64 {
+        self.max_x_grid.saturating_add(self.max_y_grid)
+    }
+
+    /// Fits every rover in the mission with the same camera.
+    pub fn with_camera(mut self, camera: camera::Camera) -> Config {
+        for instruction in self.instructions.iter_mut() {
+            instruction.camera = Some(camera);
+        }
+        self
+    }
+
+    /// How many rovers the mission lands.
+    pub fn rovers(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// The id the mission file gave the rover, if it gave one.
+    pub fn rover_id(&self, rover: usize) -> Option<&str> {
+        self.instructions.get(rover)?.id.as_deref()
+    }
+
+    /// What output names the rover: its id if it has one, or else its landing index.
+    pub fn rover_name(&self, rover: usize) -> String {
+        match self.rover_id(rover) {
+            Some(id) => id.to_string(),
+            None => rover.to_string(),
+        }
+    }
+
+    pub fn max_x_grid(&self) -> u64 {
+        self.max_x_grid
+    }
+
+    pub fn max_y_grid(&self) -> u64 {
+        self.max_y_grid
+    }
+
+    /// Compiles each rover's program, in landing order.
+    pub fn bytecode(&self) -> Vec<bytecode::Bytecode> {
+        self.instructions
+            .iter()
+            .map(|instruction| bytecode::compile(&instruction.program))
+            .collect()
+    }
+
+    /// Predicts every violation the mission would run into on the terrain with the given
+    /// kind of vehicle, without deploying anything.
+    pub fn analyse<T: Terrain + Clone, V: Vehicle>(
+        &self,
+        terrain: &T,
+    ) -> Result<Vec<analysis::Violation>, ParseError> {
+        analysis::analyse::<T, V>(self, terrain)
+    }
+
+    /// Shortens every rover's program without changing the cells it visits, returning
+    /// how much was saved for each rover in landing order. The program is checked first,
+    /// so optimising never turns a mission the vehicles cannot follow into one they can.
+    pub fn optimise<V: Vehicle>(&mut self) -> Result<Vec<optimise::Savings>, ParseError> {
+        self.check::<V>()?;
+        Ok(self
+            .instructions
+            .iter_mut()
+            .map(|instruction| {
+                let optimised = optimise::optimise::<V>(&instruction.program);
+                let savings = optimise::Savings::new(&instruction.program, &optimised);
+                instruction.program = optimised;
+                savings
+            })
+            .collect())
+    }
+
+    /// Checks every rover's bearing and commands before anything lands, so a mission that
+    /// asks for something the vehicles cannot do fails without moving any of them.
+    fn check<V: Vehicle>(&self) -> Result<(), ParseError> {
+        for instruction in &self.instructions {
+            V::check_bearing(instruction.bearing, self.compass).map_err(|e| ParseError::new(&e))?;
+            for command in program::commands(&instruction.program) {
+                V::check_command(command, self.compass).map_err(|e| ParseError::new(&e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads one command line argument, naming it in the error if it is missing or does not
+/// parse.
+fn argument<T>(arg: Option<&String>, name: &str) -> Result<T, ParseError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let arg = arg.ok_or_else(|| ParseError::new(&format!("{} not given", name)))?;
+    arg.parse()
+        .map_err(|e| ParseError::new(&format!("could not read {} '{}': {}", name, arg, e)))
 }
 
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct RoverInstructions {
     starting_x: u64,
     starting_y: u64,
     bearing: rover::Bearing,
-    commands: Vec<Command>,
+    program: Vec<program::Instruction>,
+    camera: Option<camera::Camera>,
+    id: Option<String>,
 }
 
 impl RoverInstructions {
@@ -108,35 +732,102 @@ impl RoverInstructions {
         starting_x: u64,
         starting_y: u64,
         bearing: rover::Bearing,
-        commands: Vec<Command>,
+        program: Vec<program::Instruction>,
     ) -> RoverInstructions {
         RoverInstructions {
             starting_x,
             starting_y,
             bearing,
-            commands,
+            program,
+            camera: None,
+            id: None,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialOrd, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
+    #[cfg_attr(feature = "serde", serde(rename = "M"))]
     MoveForward,
+    #[cfg_attr(feature = "serde", serde(rename = "B"))]
+    MoveBackward,
+    #[cfg_attr(feature = "serde", serde(rename = "R"))]
     RightTurn,
+    #[cfg_attr(feature = "serde", serde(rename = "L"))]
     LeftTurn,
+    #[cfg_attr(feature = "serde", serde(rename = ">"))]
+    HalfRightTurn,
+    #[cfg_attr(feature = "serde", serde(rename = "<"))]
+    HalfLeftTurn,
+    #[cfg_attr(feature = "serde", serde(rename = "P"))]
+    TakePhoto,
 }
 
 impl Command {
+    /// Every command, in the order of their letters in the challenge and its extensions.
+    pub const ALL: [Command; 7] = [
+        Command::MoveForward,
+        Command::MoveBackward,
+        Command::RightTurn,
+        Command::LeftTurn,
+        Command::HalfRightTurn,
+        Command::HalfLeftTurn,
+        Command::TakePhoto,
+    ];
+
     fn new(c: char) -> Result<Command, String> {
-        match c {
+        match c.to_ascii_uppercase() {
             'M' => Ok(Command::MoveForward),
+            'B' => Ok(Command::MoveBackward),
             'R' => Ok(Command::RightTurn),
             'L' => Ok(Command::LeftTurn),
+            '>' => Ok(Command::HalfRightTurn),
+            '<' => Ok(Command::HalfLeftTurn),
+            'P' => Ok(Command::TakePhoto),
             _ => Err(String::from("could not parse command")),
         }
     }
 }
 
+/// Accepts a command's letter in either case, or its name in any case, such as `forward`,
+/// `MoveForward`, `half-left` or `photo`.
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Command::new(c).map_err(|_| format!("unknown command '{}'", s));
+        }
+        match rover::normalise_name(s).as_str() {
+            "forward" | "move" | "moveforward" => Ok(Command::MoveForward),
+            "backward" | "back" | "movebackward" => Ok(Command::MoveBackward),
+            "right" | "rightturn" | "turnright" => Ok(Command::RightTurn),
+            "left" | "leftturn" | "turnleft" => Ok(Command::LeftTurn),
+            "halfright" | "halfrightturn" => Ok(Command::HalfRightTurn),
+            "halfleft" | "halfleftturn" => Ok(Command::HalfLeftTurn),
+            "photo" | "takephoto" => Ok(Command::TakePhoto),
+            _ => Err(format!("unknown command '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let c = match self {
+            Command::MoveForward => 'M',
+            Command::MoveBackward => 'B',
+            Command::RightTurn => 'R',
+            Command::LeftTurn => 'L',
+            Command::HalfRightTurn => '>',
+            Command::HalfLeftTurn => '<',
+            Command::TakePhoto => 'P',
+        };
+        write!(f, "{}", c)
+    }
+}
+
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub struct ParseError {
     details: String,
@@ -165,6 +856,7 @@ impl Error for ParseError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::program::Instruction;
     use crate::rover::Bearing;
 
     #[test]
@@ -182,16 +874,23 @@ mod tests {
         let expected = Config {
             max_x_grid: 5,
             max_y_grid: 5,
+            compass: rover::Compass::FourPoint,
+            budget: None,
+            trace: false,
+            coverage: true,
+            obstacles: vec![],
             instructions: vec![RoverInstructions {
                 starting_x: 3,
                 starting_y: 3,
                 bearing: Bearing::North,
-                commands: vec![
-                    Command::MoveForward,
-                    Command::RightTurn,
-                    Command::LeftTurn,
-                    Command::MoveForward,
+                program: vec![
+                    Instruction::Command(Command::MoveForward),
+                    Instruction::Command(Command::RightTurn),
+                    Instruction::Command(Command::LeftTurn),
+                    Instruction::Command(Command::MoveForward),
                 ],
+                camera: None,
+                id: None,
             }],
         };
 
@@ -219,28 +918,37 @@ mod tests {
         let expected = Config {
             max_x_grid: 5,
             max_y_grid: 5,
+            compass: rover::Compass::FourPoint,
+            budget: None,
+            trace: false,
+            coverage: true,
+            obstacles: vec![],
             instructions: vec![
                 RoverInstructions {
                     starting_x: 3,
                     starting_y: 3,
                     bearing: Bearing::North,
-                    commands: vec![
-                        Command::MoveForward,
-                        Command::RightTurn,
-                        Command::LeftTurn,
-                        Command::MoveForward,
+                    program: vec![
+                        Instruction::Command(Command::MoveForward),
+                        Instruction::Command(Command::RightTurn),
+                        Instruction::Command(Command::LeftTurn),
+                        Instruction::Command(Command::MoveForward),
                     ],
+                    camera: None,
+                    id: None,
                 },
                 RoverInstructions {
                     starting_x: 3,
                     starting_y: 3,
                     bearing: Bearing::North,
-                    commands: vec![
-                        Command::MoveForward,
-                        Command::RightTurn,
-                        Command::LeftTurn,
-                        Command::MoveForward,
+                    program: vec![
+                        Instruction::Command(Command::MoveForward),
+                        Instruction::Command(Command::RightTurn),
+                        Instruction::Command(Command::LeftTurn),
+                        Instruction::Command(Command::MoveForward),
                     ],
+                    camera: None,
+                    id: None,
                 },
             ],
         };
@@ -249,4 +957,151 @@ mod tests {
 
         assert_eq!(expected, config);
     }
+
+    #[test]
+    fn budget_only_looping_programs_by_default() {
+        let args: Vec<String> = vec!["test", "5", "5", "0", "0", "N", "MMM"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let config = Config::new(&args).expect("should create config");
+        let looping = program::parse("while not edge { M }").expect("should parse");
+
+        assert_eq!(u64::MAX, config.budget(&config.instructions[0].program));
+        assert_eq!(program::MAX_COMMANDS, config.budget(&looping));
+
+        let config = config.with_budget(10);
+
+        assert_eq!(10, config.budget(&config.instructions[0].program));
+        assert_eq!(10, config.budget(&looping));
+    }
+
+    #[test]
+    fn parse_backward_command() {
+        assert_eq!(Ok(Command::MoveBackward), Command::new('B'));
+    }
+
+    #[test]
+    fn parse_photo_command() {
+        assert_eq!(Ok(Command::TakePhoto), Command::new('P'));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_config() {
+        let args: Vec<String> = vec![
+            "test",
+            "5",
+            "5",
+            "1",
+            "2",
+            "N",
+            "(MR)2P",
+            "3",
+            "3",
+            "E",
+            "while not edge { M }",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let config = Config::new(&args)
+            .expect("should create config")
+            .with_obstacle(rover::Coordinates::new(0, 4));
+
+        let json = serde_json::to_string(&config).expect("should serialize");
+
+        assert_eq!(
+            config,
+            serde_json::from_str(&json).expect("should deserialize")
+        );
+        assert_eq!(
+            r#"["M","R",">","P"]"#,
+            serde_json::to_string(&[
+                Command::MoveForward,
+                Command::RightTurn,
+                Command::HalfRightTurn,
+                Command::TakePhoto
+            ])
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn every_command_round_trips_through_every_name() {
+        for command in Command::ALL.iter() {
+            let letter = command.to_string();
+            let long = format!("{:?}", command);
+            let names = [
+                letter.clone(),
+                letter.to_lowercase(),
+                long.clone(),
+                long.to_lowercase(),
+                long.to_uppercase(),
+            ];
+            for name in names.iter() {
+                assert_eq!(Ok(*command), name.parse::<Command>(), "parsing '{}'", name);
+            }
+        }
+        assert_eq!(Ok(Command::HalfLeftTurn), "half-left".parse());
+        assert!("X".parse::<Command>().is_err());
+        assert!("sideways".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parse_lowercase_commands() {
+        let args: Vec<String> = vec!["test", "5", "5", "1", "2", "north", "lmlmlmlmm"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let config = Config::new(&args).expect("should create config");
+
+        assert_eq!(Bearing::North, config.instructions[0].bearing);
+        assert_eq!(
+            Some(&Instruction::Command(Command::LeftTurn)),
+            config.instructions[0].program.first()
+        );
+    }
+
+    #[test]
+    fn reject_bad_arguments_with_parse_errors() {
+        let cases = [
+            (
+                vec!["5", "5", "1", "2", "N"],
+                "can't have less than 6 arguments",
+            ),
+            (
+                vec!["x", "5", "1", "2", "N", "M"],
+                "could not read first argument 'x': invalid digit found in string",
+            ),
+            (
+                vec!["5", "5", "1", "-2", "N", "M"],
+                "could not read starting y '-2': invalid digit found in string",
+            ),
+            (
+                vec!["5", "5", "1", "2", "Q", "M"],
+                "could not read bearing 'Q': unknown bearing 'Q'",
+            ),
+            (
+                vec!["5", "5", "1", "2", "N", "MXM"],
+                "unknown command 'X' at column 2",
+            ),
+            (
+                vec!["5", "5", "1", "2", "N", "[spin]"],
+                "undefined macro 'spin'",
+            ),
+        ];
+        for (args, message) in cases.iter() {
+            let args: Vec<String> = std::iter::once(&"test")
+                .chain(args.iter())
+                .map(|arg| arg.to_string())
+                .collect();
+
+            let error = Config::new(&args).expect_err("should reject arguments");
+
+            assert!(error.is::<ParseError>(), "{:?}", args);
+            assert_eq!(*message, error.to_string());
+        }
+    }
 }
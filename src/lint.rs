@@ -0,0 +1,277 @@
+use crate::analysis::{self, ViolationKind};
+use crate::mission::{self, Format};
+use crate::rover::{Compass, Vehicle};
+use crate::terrain::Terrain;
+use crate::{program, Config};
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// Worth a look, but the mission can still run.
+    Warning,
+    /// The mission would fail or could not be read.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// What the mission would do wrong, or `None` for a problem with the file itself.
+    pub violation: Option<ViolationKind>,
+}
+
+impl Diagnostic {
+    fn error(message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+            violation: None,
+        }
+    }
+
+    fn violation(message: String, violation: ViolationKind) -> Diagnostic {
+        Diagnostic {
+            violation: Some(violation),
+            ..Diagnostic::error(message)
+        }
+    }
+
+    fn warning(message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            message,
+            violation: None,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Reports every problem with a mission file without running it: syntax errors first,
+/// then, once the whole file reads, anything that would go wrong on the terrain. The
+/// terrain is made to the size the mission gives, as `run` makes it.
+pub fn lint<T: Terrain + Clone, V: Vehicle>(
+    source: &str,
+    format: Format,
+    compass: Compass,
+    terrain: fn(u64, u64) -> T,
+) -> Vec<Diagnostic> {
+    match mission::parse_all(source, format) {
+        Ok(config) => {
+            let terrain = terrain(config.max_x_grid(), config.max_y_grid());
+            lint_config::<T, V>(&config.with_compass(compass), &terrain)
+        }
+        Err(errors) => errors
+            .iter()
+            .map(|e| Diagnostic::error(e.to_string()))
+            .collect(),
+    }
+}
+
+/// Reports every problem with a mission that has already been read.
+pub fn lint_config<T: Terrain + Clone, V: Vehicle>(
+    config: &Config,
+    terrain: &T,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for (id, instruction) in config.instructions.iter().enumerate() {
+        if let Err(e) = V::check_bearing(instruction.bearing, config.compass) {
+            diagnostics.push(Diagnostic::error(format!("rover {}: {}", id, e)));
+        }
+        // Commands are named rather than numbered, as "command N" elsewhere counts the
+        // commands a rover carries out, not where they are written.
+        let mut reported = vec![];
+        for command in program::commands(&instruction.program) {
+            if let Err(e) = V::check_command(command, config.compass) {
+                if !reported.contains(&command) {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "rover {} command {}: {}",
+                        id, command, e
+                    )));
+                    reported.push(command);
+                }
+            }
+        }
+        if instruction.program.is_empty() {
+            diagnostics.push(Diagnostic::warning(format!("rover {} has no commands", id)));
+        }
+    }
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        return diagnostics;
+    }
+
+    let violations = match analysis::analyse::<T, V>(config, terrain) {
+        Ok(violations) => violations,
+        Err(e) => {
+            diagnostics.push(Diagnostic::error(e.to_string()));
+            return diagnostics;
+        }
+    };
+
+    // A rover may land where an earlier one landed once that rover has moved away, so
+    // sharing a landing cell is only worth a warning. The analysis reports the landings
+    // that would collide.
+    for (id, instruction) in config.instructions.iter().enumerate() {
+        let earlier = config.instructions[..id].iter().position(|other| {
+            (other.starting_x, other.starting_y) == (instruction.starting_x, instruction.starting_y)
+        });
+        let collides = violations
+            .iter()
+            .any(|v| v.rover == id && v.command.is_none());
+        if let Some(other) = earlier.filter(|_| !collides) {
+            diagnostics.push(Diagnostic::warning(format!(
+                "rover {} landing: shares landing cell {} {} with rover {}",
+                id, instruction.starting_x, instruction.starting_y, other
+            )));
+        }
+    }
+
+    diagnostics.extend(
+        violations
+            .iter()
+            .map(|v| Diagnostic::violation(v.to_string(), v.kind)),
+    );
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::{HexPlateau, HexRover};
+    use crate::plateau::Plateau;
+    use crate::rover::Rover;
+
+    fn lint_square(source: &str, format: Format, compass: Compass) -> Vec<Diagnostic> {
+        lint::<_, Rover>(source, format, compass, Plateau::new)
+    }
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn clean_mission_has_no_diagnostics() {
+        let source = "5 5\n1 2 N\nLMLMLMLMM\n3 3 E\nMMRMMRMRRM\n";
+
+        assert_eq!(
+            Vec::<Diagnostic>::new(),
+            lint_square(source, Format::Text, Compass::FourPoint)
+        );
+    }
+
+    #[test]
+    fn report_every_syntax_error() {
+        let source = "5 5\n1 2 Q\nMM\n3 3 E\nMXM\n";
+
+        assert_eq!(
+            vec![
+                "error: line 2: unknown bearing 'Q'",
+                "error: line 5: unknown command 'X' at column 2",
+            ],
+            messages(&lint_square(source, Format::Text, Compass::FourPoint))
+        );
+    }
+
+    #[test]
+    fn report_every_mission_problem() {
+        let source = "5 5\n1 2 N\nM\n1 2 E\nM\n6 0 N\nM\n0 0 N\nMMMMMM\n";
+
+        assert_eq!(
+            vec![
+                "warning: rover 1 landing: shares landing cell 1 2 with rover 0",
+                "error: rover 2 landing: leaves the plateau at 6 0",
+                "error: rover 3 command 5: leaves the plateau at 0 6",
+            ],
+            messages(&lint_square(source, Format::Text, Compass::FourPoint))
+        );
+    }
+
+    #[test]
+    fn classify_what_the_mission_would_do_wrong() {
+        let source = "5 5\n1 2 N\nM\n1 2 E\nM\n0 0 N\nMMMMMM\n";
+
+        let violations: Vec<_> = lint_square(source, Format::Text, Compass::FourPoint)
+            .iter()
+            .map(|d| d.violation)
+            .collect();
+
+        assert_eq!(vec![None, Some(ViolationKind::LeavesPlateau)], violations);
+    }
+
+    #[test]
+    fn report_landings_on_rovers_that_have_not_moved_away() {
+        let source = "5 5\n1 2 N\nR\n1 2 E\nM\n";
+
+        let diagnostics = lint_square(source, Format::Text, Compass::FourPoint);
+
+        assert_eq!(
+            vec!["error: rover 1 landing: hits rover 0 at 1 2"],
+            messages(&diagnostics)
+        );
+        assert_eq!(
+            Some(ViolationKind::HitsRover(Some(0))),
+            diagnostics[0].violation
+        );
+    }
+
+    #[test]
+    fn warn_about_rovers_without_commands() {
+        let source = r#"{
+            "plateau": { "x": 5, "y": 5 },
+            "rovers": [{ "id": "idle", "x": 1, "y": 1, "bearing": "N", "commands": "" }]
+        }"#;
+
+        let diagnostics = lint_square(source, Format::Json, Compass::FourPoint);
+
+        assert_eq!(
+            vec!["warning: rover 0 has no commands"],
+            messages(&diagnostics)
+        );
+    }
+
+    #[test]
+    fn report_commands_the_compass_cannot_follow() {
+        let source = "5 5\n1 2 NE\nM>M>\n";
+
+        let diagnostics = lint_square(source, Format::Text, Compass::FourPoint);
+
+        assert_eq!(
+            vec![
+                "error: rover 0: diagonal bearings need an eight point compass",
+                "error: rover 0 command >: half turns need an eight point compass",
+            ],
+            messages(&diagnostics)
+        );
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+        assert!(diagnostics.iter().all(|d| d.violation.is_none()));
+    }
+
+    #[test]
+    fn lint_against_the_terrain_and_vehicle_the_mission_runs_with() {
+        let source = "3 3\n1 1 SE\nM\n";
+
+        let diagnostics =
+            lint::<_, HexRover>(source, Format::Text, Compass::FourPoint, HexPlateau::new);
+
+        assert_eq!(Vec::<Diagnostic>::new(), diagnostics);
+        assert_eq!(
+            1,
+            lint_square(source, Format::Text, Compass::FourPoint).len()
+        );
+    }
+}
@@ -0,0 +1,654 @@
+use crate::program::{self, Macros};
+use crate::rover::Coordinates;
+use crate::{Config, ParseError, RoverInstructions};
+use serde_json::{Map, Value};
+
+/// Parses a mission written the way the challenge lays out its input: the plateau's upper
+/// right coordinates on the first line, then two lines per rover giving its position and
+/// its commands. Blank lines and lines starting with `#` are ignored.
+///
+/// Lines of the form `def name = commands` define macros that rover programs, and other
+/// macros, can call with `[name]`. Definitions may appear anywhere in the file.
+pub fn parse(source: &str) -> Result<Config, ParseError> {
+    first(parse_text(source))
+}
+
+/// The formats a mission file can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Picks the format from a file's extension, falling back to the challenge's text
+    /// layout.
+    pub fn from_path(path: &str) -> Format {
+        if path.ends_with(".json") {
+            Format::Json
+        } else if path.ends_with(".toml") {
+            Format::Toml
+        } else {
+            Format::Text
+        }
+    }
+}
+
+/// Parses a mission in the given format, reporting every problem found instead of
+/// stopping at the first. Problems that leave the rest of the file unreadable, such as
+/// invalid JSON, are reported alone.
+pub fn parse_all(source: &str, format: Format) -> Result<Config, Vec<ParseError>> {
+    match format {
+        Format::Text => parse_text(source),
+        Format::Json => {
+            let value: Value = serde_json::from_str(source)
+                .map_err(|e| vec![ParseError::new(&format!("invalid JSON: {}", e))])?;
+            from_value(&value, &JSON)
+        }
+        Format::Toml => {
+            let value: toml::Value = toml::from_str(source)
+                .map_err(|e| vec![ParseError::new(&format!("invalid TOML: {}", e))])?;
+            let value = serde_json::to_value(value)
+                .map_err(|e| vec![ParseError::new(&format!("invalid TOML: {}", e))])?;
+            from_value(&value, &TOML)
+        }
+    }
+}
+
+fn first(result: Result<Config, Vec<ParseError>>) -> Result<Config, ParseError> {
+    result.map_err(|mut errors| errors.remove(0))
+}
+
+/// Keeps the value, or records the error and carries on without it.
+fn collect<T>(errors: &mut Vec<ParseError>, result: Result<T, ParseError>) -> Option<T> {
+    result.map_err(|e| errors.push(e)).ok()
+}
+
+fn parse_text(source: &str) -> Result<Config, Vec<ParseError>> {
+    let mut errors = vec![];
+    let mut macros = Macros::new();
+    let mut lines = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.strip_prefix("def ") {
+            Some(definition) => match definition.split_once('=') {
+                Some((name, body)) => {
+                    collect(&mut errors, macros.define(name.trim(), body, number));
+                }
+                None => errors.push(ParseError::new(&format!(
+                    "line {}: expected 'def name = commands'",
+                    number
+                ))),
+            },
+            None => lines.push((number, line)),
+        }
+    }
+
+    let mut lines = lines.into_iter();
+    let (number, plateau) = match lines.next() {
+        Some(line) => line,
+        None => {
+            errors.push(ParseError::new("mission has no plateau"));
+            return Err(errors);
+        }
+    };
+    let plateau = match plateau.split_whitespace().collect::<Vec<_>>()[..] {
+        [x, y] => (
+            collect(&mut errors, parse_number(x, number)),
+            collect(&mut errors, parse_number(y, number)),
+        ),
+        _ => {
+            errors.push(ParseError::new(&format!(
+                "line {}: expected plateau as 'x y'",
+                number
+            )));
+            (None, None)
+        }
+    };
+
+    let mut instructions = vec![];
+    while let Some((number, position)) = lines.next() {
+        let position = match position.split_whitespace().collect::<Vec<_>>()[..] {
+            [x, y, bearing] => (
+                collect(&mut errors, parse_number(x, number)),
+                collect(&mut errors, parse_number(y, number)),
+                collect(
+                    &mut errors,
+                    bearing
+                        .parse()
+                        .map_err(|e: String| ParseError::new(&format!("line {}: {}", number, e))),
+                ),
+            ),
+            _ => {
+                errors.push(ParseError::new(&format!(
+                    "line {}: expected rover position as 'x y bearing'",
+                    number
+                )));
+                (None, None, None)
+            }
+        };
+        let (number, source) = match lines.next() {
+            Some(line) => line,
+            None => {
+                errors.push(ParseError::new(&format!(
+                    "line {}: rover has no commands",
+                    number
+                )));
+                break;
+            }
+        };
+        let program = collect(
+            &mut errors,
+            program::parse(source)
+                .and_then(|program| macros.resolve(&program))
+                .map_err(|e| ParseError::new(&format!("line {}: {}", number, e))),
+        );
+        if let ((Some(x), Some(y), Some(bearing)), Some(program)) = (position, program) {
+            instructions.push(RoverInstructions::new(x, y, bearing, program));
+        }
+    }
+
+    match plateau {
+        (Some(max_x_grid), Some(max_y_grid)) if errors.is_empty() => Ok(Config::from_instructions(
+            max_x_grid,
+            max_y_grid,
+            instructions,
+        )),
+        _ => Err(errors),
+    }
+}
+
+fn parse_number(s: &str, line: usize) -> Result<u64, ParseError> {
+    s.parse()
+        .map_err(|_| ParseError::new(&format!("line {}: '{}' is not a whole number", line, s)))
+}
+
+/// Parses a mission written as JSON, such as
+///
+/// ```json
+/// {
+///     "plateau": { "x": 5, "y": 5 },
+///     "obstacles": [{ "x": 0, "y": 3 }],
+///     "rovers": [{ "id": "spirit", "x": 1, "y": 2, "bearing": "N", "commands": "LMLMLMLMM" }]
+/// }
+/// ```
+///
+/// Obstacles are optional, and each rover may carry a `metadata` object, which the
+/// mission ignores. Errors name the path to the offending field, as in
+/// `rovers[2].bearing: expected one of N,NE,E,SE,S,SW,W,NW`.
+pub fn parse_json(source: &str) -> Result<Config, ParseError> {
+    first(parse_all(source, Format::Json))
+}
+
+/// Parses a mission written as TOML, such as
+///
+/// ```toml
+/// [plateau]
+/// x = 5
+/// y = 5
+///
+/// [[obstacle]]
+/// x = 0
+/// y = 3
+///
+/// [[rover]]
+/// id = "spirit"
+/// x = 1
+/// y = 2
+/// bearing = "N"
+/// commands = "LMLMLMLMM"
+/// metadata = { team = "blue" }
+/// ```
+///
+/// The schema and errors are the same as for JSON missions, except that obstacles and
+/// rovers are repeated `[[obstacle]]` and `[[rover]]` tables. Each rover may carry a
+/// `metadata` table, which the mission ignores.
+pub fn parse_toml(source: &str) -> Result<Config, ParseError> {
+    first(parse_all(source, Format::Toml))
+}
+
+/// What a mission format calls its lists of obstacles and rovers. JSON uses plural
+/// arrays, while TOML reads better as repeated `[[rover]]` tables.
+struct Layout {
+    obstacles: &'static str,
+    rovers: &'static str,
+}
+
+const JSON: Layout = Layout {
+    obstacles: "obstacles",
+    rovers: "rovers",
+};
+
+const TOML: Layout = Layout {
+    obstacles: "obstacle",
+    rovers: "rover",
+};
+
+/// Checks a mission already read into a JSON value against the mission schema, which
+/// every structured format shares.
+fn from_value(value: &Value, layout: &Layout) -> Result<Config, Vec<ParseError>> {
+    let mission = object(
+        value,
+        "mission",
+        &["plateau", layout.obstacles, layout.rovers],
+    )
+    .map_err(|e| vec![e])?;
+    let mut errors = vec![];
+
+    let plateau = collect(
+        &mut errors,
+        field(mission, "", "plateau").and_then(|plateau| object(plateau, "plateau", &["x", "y"])),
+    );
+    let (max_x_grid, max_y_grid) = match plateau {
+        Some(plateau) => (
+            collect(
+                &mut errors,
+                field(plateau, "plateau", "x").and_then(|x| number(x, "plateau.x")),
+            ),
+            collect(
+                &mut errors,
+                field(plateau, "plateau", "y").and_then(|y| number(y, "plateau.y")),
+            ),
+        ),
+        None => (None, None),
+    };
+
+    let mut obstacles = vec![];
+    if let Some(value) = mission.get(layout.obstacles) {
+        let values = collect(&mut errors, array(value, layout.obstacles));
+        for (index, value) in values.into_iter().flatten().enumerate() {
+            let path = format!("{}[{}]", layout.obstacles, index);
+            let obstacle = match collect(&mut errors, object(value, &path, &["x", "y"])) {
+                Some(obstacle) => obstacle,
+                None => continue,
+            };
+            let x = collect(&mut errors, coordinate(obstacle, &path, "x"));
+            let y = collect(&mut errors, coordinate(obstacle, &path, "y"));
+            if let (Some(x), Some(y)) = (x, y) {
+                obstacles.push(Coordinates::new(x, y));
+            }
+        }
+    }
+
+    let rovers = collect(
+        &mut errors,
+        field(mission, "", layout.rovers).and_then(|rovers| array(rovers, layout.rovers)),
+    );
+    if rovers.is_some_and(|rovers| rovers.is_empty()) {
+        errors.push(ParseError::new(&format!(
+            "{}: expected at least one rover",
+            layout.rovers
+        )));
+    }
+    let mut instructions: Vec<RoverInstructions> = vec![];
+    let mut ids: Vec<&str> = vec![];
+    for (index, value) in rovers.into_iter().flatten().enumerate() {
+        let path = format!("{}[{}]", layout.rovers, index);
+        let fields = ["id", "x", "y", "bearing", "commands", "metadata"];
+        let rover = match collect(&mut errors, object(value, &path, &fields)) {
+            Some(rover) => rover,
+            None => continue,
+        };
+        let at = |name: &str| format!("{}.{}", path, name);
+
+        let id = collect(
+            &mut errors,
+            field(rover, &path, "id").and_then(|id| string(id, &at("id"))),
+        );
+        if let Some(id) = id {
+            if ids.contains(&id) {
+                errors.push(ParseError::new(&format!(
+                    "{}: duplicate id '{}'",
+                    at("id"),
+                    id
+                )));
+            }
+            ids.push(id);
+        }
+        let x = collect(&mut errors, coordinate(rover, &path, "x"));
+        let y = collect(&mut errors, coordinate(rover, &path, "y"));
+        let bearing = collect(
+            &mut errors,
+            field(rover, &path, "bearing")
+                .and_then(|bearing| string(bearing, &at("bearing")))
+                .and_then(|bearing| {
+                    bearing.parse().map_err(|_| {
+                        ParseError::new(&format!(
+                            "{}: expected one of N,NE,E,SE,S,SW,W,NW",
+                            at("bearing")
+                        ))
+                    })
+                }),
+        );
+        let program = collect(
+            &mut errors,
+            field(rover, &path, "commands")
+                .and_then(|commands| string(commands, &at("commands")))
+                .and_then(|source| {
+                    program::parse(source)
+                        .and_then(|program| Macros::new().resolve(&program))
+                        .map_err(|e| ParseError::new(&format!("{}: {}", at("commands"), e)))
+                }),
+        );
+        if let Some(metadata) = rover.get("metadata") {
+            if !metadata.is_object() {
+                errors.push(ParseError::new(&format!(
+                    "{}: expected an object",
+                    at("metadata")
+                )));
+            }
+        }
+
+        if let (Some(id), Some(x), Some(y), Some(bearing), Some(program)) =
+            (id, x, y, bearing, program)
+        {
+            let mut instruction = RoverInstructions::new(x, y, bearing, program);
+            instruction.id = Some(id.to_string());
+            instructions.push(instruction);
+        }
+    }
+
+    match (max_x_grid, max_y_grid) {
+        (Some(max_x_grid), Some(max_y_grid)) if errors.is_empty() => {
+            let mut config = Config::from_instructions(max_x_grid, max_y_grid, instructions);
+            for obstacle in obstacles {
+                config = config.with_obstacle(obstacle);
+            }
+            Ok(config)
+        }
+        _ => Err(errors),
+    }
+}
+
+/// Reads one coordinate field of an obstacle or rover.
+fn coordinate(object: &Map<String, Value>, path: &str, name: &str) -> Result<u64, ParseError> {
+    field(object, path, name).and_then(|value| number(value, &format!("{}.{}", path, name)))
+}
+
+/// Reads a JSON object, rejecting any field the schema does not list.
+fn object<'a>(
+    value: &'a Value,
+    path: &str,
+    fields: &[&str],
+) -> Result<&'a Map<String, Value>, ParseError> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| ParseError::new(&format!("{}: expected an object", path)))?;
+    match object.keys().find(|key| !fields.contains(&key.as_str())) {
+        Some(key) if path == "mission" => Err(ParseError::new(&format!("{}: unknown field", key))),
+        Some(key) => Err(ParseError::new(&format!("{}.{}: unknown field", path, key))),
+        None => Ok(object),
+    }
+}
+
+fn field<'a>(
+    object: &'a Map<String, Value>,
+    path: &str,
+    name: &str,
+) -> Result<&'a Value, ParseError> {
+    object.get(name).ok_or_else(|| {
+        let path = if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        };
+        ParseError::new(&format!("{}: missing field", path))
+    })
+}
+
+fn array<'a>(value: &'a Value, path: &str) -> Result<&'a Vec<Value>, ParseError> {
+    value
+        .as_array()
+        .ok_or_else(|| ParseError::new(&format!("{}: expected an array", path)))
+}
+
+fn string<'a>(value: &'a Value, path: &str) -> Result<&'a str, ParseError> {
+    value
+        .as_str()
+        .ok_or_else(|| ParseError::new(&format!("{}: expected a string", path)))
+}
+
+fn number(value: &Value, path: &str) -> Result<u64, ParseError> {
+    value
+        .as_u64()
+        .ok_or_else(|| ParseError::new(&format!("{}: expected a whole number", path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rover::Bearing;
+    use crate::Command;
+
+    #[test]
+    fn parse_challenge_input() {
+        let source = "5 5\n1 2 N\nLMLMLMLMM\n3 3 E\nMMRMMRMRRM\n";
+
+        let args: Vec<String> = "test 5 5 1 2 N LMLMLMLMM 3 3 E MMRMMRMRRM"
+            .split(' ')
+            .map(String::from)
+            .collect();
+
+        assert_eq!(
+            Config::new(&args).expect("should create config"),
+            parse(source).expect("should parse mission")
+        );
+    }
+
+    #[test]
+    fn parse_macros() {
+        let source = "
+            # a square with sides of three
+            def side = M3R
+            def square = [side]4
+
+            5 5
+            0 0 N
+            [square]M
+        ";
+
+        let config = parse(source).expect("should parse mission");
+
+        assert_eq!(1, config.instructions.len());
+        assert_eq!(Bearing::North, config.instructions[0].bearing);
+        let commands =
+            program::expand(&config.instructions[0].program).expect("should expand program");
+        assert_eq!(17, commands.len());
+        assert_eq!(Some(&Command::MoveForward), commands.last());
+    }
+
+    #[test]
+    fn point_at_recursive_definition() {
+        let source = "def a = M[b]\ndef b = [a]\n5 5\n0 0 N\n[a]\n";
+
+        assert_eq!(
+            Err(ParseError::new(
+                "line 5: macro 'a' at line 1 calls itself through a -> b -> a"
+            )),
+            parse(source)
+        );
+    }
+
+    #[test]
+    fn point_at_bad_line() {
+        assert_eq!(
+            Err(ParseError::new(
+                "line 3: expected rover position as 'x y bearing'"
+            )),
+            parse("5 5\n\n1 2\nM\n")
+        );
+        assert_eq!(
+            Err(ParseError::new("line 2: rover has no commands")),
+            parse("5 5\n1 2 N\n")
+        );
+    }
+
+    #[test]
+    fn parse_json_mission() {
+        let source = r#"{
+            "plateau": { "x": 5, "y": 5 },
+            "obstacles": [{ "x": 0, "y": 4 }],
+            "rovers": [
+                { "id": "spirit", "x": 1, "y": 2, "bearing": "N", "commands": "LMLMLMLMM" },
+                { "id": "opportunity", "x": 3, "y": 3, "bearing": "E", "commands": "MMRMMRMRRM" }
+            ]
+        }"#;
+
+        let config = parse_json(source).expect("should parse mission");
+        let expected = parse("5 5\n1 2 N\nLMLMLMLMM\n3 3 E\nMMRMMRMRRM\n")
+            .expect("should parse mission")
+            .with_obstacle(Coordinates::new(0, 4));
+
+        assert_eq!(expected.instructions.len(), config.instructions.len());
+        for (expected, actual) in expected.instructions.iter().zip(&config.instructions) {
+            assert_eq!(expected.program, actual.program);
+            assert_eq!(expected.bearing, actual.bearing);
+        }
+        assert_eq!(expected.obstacles, config.obstacles);
+        assert_eq!(Some("opportunity"), config.rover_id(1));
+    }
+
+    #[test]
+    fn point_at_bad_json_field() {
+        let mission = |rovers: &str| {
+            parse_json(&format!(
+                r#"{{ "plateau": {{ "x": 5, "y": 5 }}, "rovers": [{}] }}"#,
+                rovers
+            ))
+        };
+        let rover = r#"{ "id": "a", "x": 1, "y": 2, "bearing": "N", "commands": "M" }"#;
+
+        assert_eq!(
+            Err(ParseError::new(
+                "rovers[2].bearing: expected one of N,NE,E,SE,S,SW,W,NW"
+            )),
+            mission(&format!(
+                r#"{}, {}, {{ "id": "c", "x": 1, "y": 2, "bearing": "Q", "commands": "M" }}"#,
+                rover,
+                rover.replace("\"a\"", "\"b\"")
+            ))
+        );
+        assert_eq!(
+            Err(ParseError::new("rovers[1].id: duplicate id 'a'")),
+            mission(&format!("{}, {}", rover, rover))
+        );
+        assert_eq!(
+            Err(ParseError::new("rovers[0].x: expected a whole number")),
+            mission(&rover.replace("1,", "-1,"))
+        );
+        assert_eq!(
+            Err(ParseError::new(
+                "rovers[0].commands: unknown command 'X' at column 1"
+            )),
+            mission(&rover.replace("\"M\"", "\"X\""))
+        );
+        assert_eq!(
+            Err(ParseError::new("rovers[0].speed: unknown field")),
+            mission(&rover.replace("}", r#", "speed": 2 }"#))
+        );
+        assert_eq!(
+            Err(ParseError::new("rovers: missing field")),
+            parse_json(r#"{ "plateau": { "x": 5, "y": 5 } }"#)
+        );
+    }
+
+    #[test]
+    fn parse_toml_mission() {
+        let source = r#"
+            # hand written mission
+            [plateau]
+            x = 5
+            y = 5
+
+            [[obstacle]]
+            x = 0
+            y = 4
+
+            [[rover]]
+            id = "spirit"
+            x = 1
+            y = 2
+            bearing = "N"
+            commands = "LMLMLMLMM"
+
+            [rover.metadata]
+            team = "blue"
+
+            [[rover]]
+            id = "opportunity"
+            x = 3
+            y = 3
+            bearing = "E"
+            commands = "MMRMMRMRRM"
+        "#;
+        let json = r#"{
+            "plateau": { "x": 5, "y": 5 },
+            "obstacles": [{ "x": 0, "y": 4 }],
+            "rovers": [
+                { "id": "spirit", "x": 1, "y": 2, "bearing": "N", "commands": "LMLMLMLMM" },
+                { "id": "opportunity", "x": 3, "y": 3, "bearing": "E", "commands": "MMRMMRMRRM" }
+            ]
+        }"#;
+
+        assert_eq!(
+            parse_json(json).expect("should parse mission"),
+            parse_toml(source).expect("should parse mission")
+        );
+    }
+
+    #[test]
+    fn point_at_bad_toml_field() {
+        let source = "[plateau]\nx = 5\ny = 5\n\n[[rover]]\nid = \"a\"\nx = 1\ny = 2\nbearing = \"N\"\ncommands = 3\n";
+
+        assert_eq!(
+            Err(ParseError::new("rover[0].commands: expected a string")),
+            parse_toml(source)
+        );
+        assert!(parse_toml("[plateau\n").is_err());
+    }
+
+    #[test]
+    fn report_every_problem() {
+        let source = "5 5\n1 2 Q\nMM\n3 x E\nMXM\n";
+
+        let errors = parse_all(source, Format::Text).expect_err("should not parse");
+
+        assert_eq!(
+            vec![
+                ParseError::new("line 2: unknown bearing 'Q'"),
+                ParseError::new("line 4: 'x' is not a whole number"),
+                ParseError::new("line 5: unknown command 'X' at column 2"),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn report_every_bad_json_field() {
+        let source = r#"{
+            "plateau": { "x": 5, "y": -5 },
+            "rovers": [
+                { "id": "a", "x": 1, "y": 2, "bearing": "Q", "commands": "M" },
+                { "id": "a", "x": 1, "y": 2, "bearing": "N", "commands": "X" }
+            ]
+        }"#;
+
+        let errors = parse_all(source, Format::Json).expect_err("should not parse");
+
+        assert_eq!(
+            vec![
+                ParseError::new("plateau.y: expected a whole number"),
+                ParseError::new("rovers[0].bearing: expected one of N,NE,E,SE,S,SW,W,NW"),
+                ParseError::new("rovers[1].id: duplicate id 'a'"),
+                ParseError::new("rovers[1].commands: unknown command 'X' at column 1"),
+            ],
+            errors
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use crate::program::{self, Instruction};
+use crate::rover::Vehicle;
+use crate::Command;
+use core::fmt;
+
+/// Turn commands in the order the optimiser prefers them, so a net turn that quarter
+/// turns can make is never rewritten with half turns.
+const TURNS: [Command; 4] = [
+    Command::RightTurn,
+    Command::LeftTurn,
+    Command::HalfRightTurn,
+    Command::HalfLeftTurn,
+];
+
+/// How much shorter the optimiser made one rover's program, in commands carried out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Savings {
+    before: u64,
+    after: u64,
+}
+
+impl Savings {
+    pub fn new(before: &[Instruction], after: &[Instruction]) -> Savings {
+        Savings {
+            before: program::executed(before),
+            after: program::executed(after),
+        }
+    }
+
+    pub fn before(&self) -> u64 {
+        self.before
+    }
+
+    pub fn after(&self) -> u64 {
+        self.after
+    }
+
+    pub fn saved(&self) -> u64 {
+        self.before - self.after
+    }
+}
+
+impl fmt::Display for Savings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {} commands ({} saved)",
+            self.before,
+            self.after,
+            self.saved()
+        )
+    }
+}
+
+/// Rewrites a program so that every run of turns between other instructions becomes the
+/// shortest run with the same net turn. Only turns are touched, so the vehicle visits
+/// exactly the same cells, in the same order, facing the same way whenever it moves,
+/// senses or takes a photo.
+pub fn optimise<V: Vehicle>(program: &[Instruction]) -> Vec<Instruction> {
+    Optimiser::<V>::new().block(program)
+}
+
+struct Optimiser<V> {
+    /// The shortest run of turns for each net turn, indexed by turn steps clockwise.
+    shortest: Vec<Vec<Command>>,
+    vehicle: std::marker::PhantomData<V>,
+}
+
+impl<V: Vehicle> Optimiser<V> {
+    fn new() -> Optimiser<V> {
+        let headings = V::HEADINGS as usize;
+        let mut shortest: Vec<Option<Vec<Command>>> = vec![None; headings];
+        shortest[0] = Some(vec![]);
+        let mut frontier = vec![0];
+        while !frontier.is_empty() {
+            let mut next = vec![];
+            for net in frontier {
+                for turn in TURNS.iter() {
+                    let steps = match V::turn_steps(turn) {
+                        Some(steps) => steps as usize,
+                        None => continue,
+                    };
+                    let reached = (net + steps) % headings;
+                    if shortest[reached].is_none() {
+                        let mut run = shortest[net].clone().unwrap_or_default();
+                        run.push(*turn);
+                        shortest[reached] = Some(run);
+                        next.push(reached);
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        Optimiser {
+            shortest: shortest
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect(),
+            vehicle: std::marker::PhantomData,
+        }
+    }
+
+    fn block(&self, program: &[Instruction]) -> Vec<Instruction> {
+        let mut optimised = vec![];
+        let mut run = vec![];
+        for instruction in program {
+            if self.net_turn(instruction).is_some() {
+                run.push(instruction.clone());
+                continue;
+            }
+            self.flush(&mut run, &mut optimised);
+            optimised.push(match instruction {
+                Instruction::Repeat(body, count) => Instruction::Repeat(self.block(body), *count),
+                Instruction::If(condition, then, otherwise) => {
+                    Instruction::If(*condition, self.block(then), self.block(otherwise))
+                }
+                Instruction::While(condition, body) => {
+                    Instruction::While(*condition, self.block(body))
+                }
+                other => other.clone(),
+            });
+        }
+        self.flush(&mut run, &mut optimised);
+        optimised
+    }
+
+    /// The net turn of an instruction that does nothing but turn, counting repeated
+    /// groups of turns.
+    fn net_turn(&self, instruction: &Instruction) -> Option<u64> {
+        match instruction {
+            Instruction::Command(command) => V::turn_steps(command),
+            Instruction::Repeat(body, count) => {
+                let mut net = 0;
+                for instruction in body {
+                    net = (net + self.net_turn(instruction)?) % V::HEADINGS;
+                }
+                Some(net * (count % V::HEADINGS) % V::HEADINGS)
+            }
+            _ => None,
+        }
+    }
+
+    /// Replaces a run of turns with the shortest run making the same net turn, leaving
+    /// runs that are already as short as they can be exactly as written.
+    fn flush(&self, run: &mut Vec<Instruction>, optimised: &mut Vec<Instruction>) {
+        let net = run
+            .iter()
+            .filter_map(|instruction| self.net_turn(instruction))
+            .fold(0, |net, steps| (net + steps) % V::HEADINGS);
+        let shortest = &self.shortest[net as usize];
+        if (shortest.len() as u64) < program::executed(run) {
+            optimised.extend(shortest.iter().map(|turn| Instruction::Command(*turn)));
+        } else {
+            optimised.append(run);
+        }
+        run.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::HexRover;
+    use crate::program::parse;
+    use crate::rover::Rover;
+
+    fn optimised<V: Vehicle>(source: &str) -> Vec<Instruction> {
+        optimise::<V>(&parse(source).expect("should parse"))
+    }
+
+    #[test]
+    fn cancel_opposite_turns() {
+        assert_eq!(parse("MM").unwrap(), optimised::<Rover>("MLRRLM"));
+    }
+
+    #[test]
+    fn collapse_full_circle() {
+        assert_eq!(parse("M").unwrap(), optimised::<Rover>("RRRRM"));
+    }
+
+    #[test]
+    fn three_rights_make_a_left() {
+        assert_eq!(parse("MLM").unwrap(), optimised::<Rover>("MRRRM"));
+    }
+
+    #[test]
+    fn merge_half_turns() {
+        assert_eq!(parse("MR>").unwrap(), optimised::<Rover>("M>>>"));
+        assert_eq!(parse("ML<").unwrap(), optimised::<Rover>("MLL>R<<"));
+    }
+
+    #[test]
+    fn merge_repeated_turns() {
+        assert_eq!(parse("MLM").unwrap(), optimised::<Rover>("M(R2)3 R M"));
+    }
+
+    #[test]
+    fn merge_counted_turns() {
+        assert_eq!(parse("MLM").unwrap(), optimised::<Rover>("MR3M"));
+        assert_eq!(parse("MRM").unwrap(), optimised::<Rover>("M(R)5M"));
+        assert_eq!(parse("MR2M").unwrap(), optimised::<Rover>("MR2M"));
+    }
+
+    #[test]
+    fn keep_turns_around_moves_and_sensors() {
+        let source = "RM(RM)3 if blocked { LL } else { P } R";
+        assert_eq!(parse(source).unwrap(), optimised::<Rover>(source));
+    }
+
+    #[test]
+    fn optimise_inside_blocks() {
+        assert_eq!(
+            parse("(MR)2 while not edge { LM }").unwrap(),
+            optimised::<Rover>("(MRRRRR)2 while not edge { RRRM }")
+        );
+    }
+
+    #[test]
+    fn optimise_hex_turns() {
+        assert_eq!(parse("MLL").unwrap(), optimised::<HexRover>("MRRRR"));
+        assert_eq!(parse("M").unwrap(), optimised::<HexRover>("MR6"));
+    }
+
+    #[test]
+    fn report_savings() {
+        let program = parse("MRRRM").unwrap();
+        let savings = Savings::new(&program, &optimise::<Rover>(&program));
+
+        assert_eq!(2, savings.saved());
+        assert_eq!("5 -> 3 commands (2 saved)", savings.to_string());
+    }
+
+    #[test]
+    fn report_savings_in_commands_carried_out() {
+        let program = parse("M(R)5M").unwrap();
+        let savings = Savings::new(&program, &optimise::<Rover>(&program));
+
+        assert_eq!("7 -> 3 commands (4 saved)", savings.to_string());
+    }
+}
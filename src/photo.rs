@@ -0,0 +1,129 @@
+use crate::rover::{Bearing, Coordinates};
+use serde_json::json;
+use std::io;
+use std::io::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhotoRecord {
+    pub rover: usize,
+    pub tick: u64,
+    pub coordinates: Coordinates,
+    pub bearing: Bearing,
+    pub imaged: Vec<Coordinates>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhotoLog {
+    records: Vec<PhotoRecord>,
+}
+
+impl PhotoLog {
+    pub fn new() -> PhotoLog {
+        PhotoLog::default()
+    }
+
+    pub fn record(&mut self, record: PhotoRecord) {
+        self.records.push(record);
+    }
+
+    pub fn records(&self) -> &[PhotoRecord] {
+        &self.records
+    }
+
+    /// Writes one row per photo. Imaged cells share a column as `x:y` pairs separated by `;`.
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "rover,tick,x,y,bearing,imaged")?;
+        for record in &self.records {
+            let imaged: Vec<String> = record
+                .imaged
+                .iter()
+                .map(|c| format!("{}:{}", c.x_coordinate, c.y_coordinate))
+                .collect();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                record.rover,
+                record.tick,
+                record.coordinates.x_coordinate,
+                record.coordinates.y_coordinate,
+                record.bearing,
+                imaged.join(";")
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let records: Vec<_> = self
+            .records
+            .iter()
+            .map(|record| {
+                let imaged: Vec<_> = record
+                    .imaged
+                    .iter()
+                    .map(|c| json!([c.x_coordinate, c.y_coordinate]))
+                    .collect();
+                json!({
+                    "rover": record.rover,
+                    "tick": record.tick,
+                    "x": record.coordinates.x_coordinate,
+                    "y": record.coordinates.y_coordinate,
+                    "bearing": record.bearing.to_string(),
+                    "imaged": imaged,
+                })
+            })
+            .collect();
+        serde_json::to_writer_pretty(&mut *writer, &records)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> PhotoLog {
+        let mut log = PhotoLog::new();
+        log.record(PhotoRecord {
+            rover: 1,
+            tick: 4,
+            coordinates: Coordinates::new(2, 3),
+            bearing: Bearing::East,
+            imaged: vec![Coordinates::new(2, 3), Coordinates::new(3, 3)],
+        });
+        log
+    }
+
+    #[test]
+    fn export_csv() {
+        let mut output = vec![];
+
+        log().write_csv(&mut output).expect("should write csv");
+
+        assert_eq!(
+            "rover,tick,x,y,bearing,imaged\n1,4,2,3,E,2:3;3:3\n",
+            String::from_utf8(output).expect("should be utf8")
+        );
+    }
+
+    #[test]
+    fn export_json() {
+        let mut output = vec![];
+
+        log().write_json(&mut output).expect("should write json");
+
+        let value: serde_json::Value =
+            serde_json::from_slice(&output).expect("should be valid json");
+        assert_eq!(
+            json!([{
+                "rover": 1,
+                "tick": 4,
+                "x": 2,
+                "y": 3,
+                "bearing": "E",
+                "imaged": [[2, 3], [3, 3]],
+            }]),
+            value
+        );
+    }
+}
@@ -1,14 +1,17 @@
 use crate::rover;
+use crate::terrain::{self, Terrain};
 use core::fmt;
 use std::collections::HashSet;
 use std::error;
 use std::error::Error;
 use Vec;
 
+#[derive(Clone)]
 pub struct Plateau {
     max_x_coordinate: u64,
     max_y_coordinate: u64,
     rovers: HashSet<rover::Coordinates>,
+    obstacles: HashSet<rover::Coordinates>,
 }
 
 impl Plateau {
@@ -17,22 +20,47 @@ impl Plateau {
             max_x_coordinate,
             max_y_coordinate,
             rovers: HashSet::new(),
+            obstacles: HashSet::new(),
         }
     }
 
+    pub fn max_x_coordinate(&self) -> u64 {
+        self.max_x_coordinate
+    }
+
+    pub fn max_y_coordinate(&self) -> u64 {
+        self.max_y_coordinate
+    }
+
     pub fn list_rovers(&self) -> Vec<rover::Coordinates> {
         self.rovers.iter().cloned().collect()
     }
 
+    pub fn list_obstacles(&self) -> Vec<rover::Coordinates> {
+        self.obstacles.iter().cloned().collect()
+    }
+
     pub fn drop_rover(&mut self, coordinates: rover::Coordinates) -> Result<(), DropError> {
-        if self.rovers.insert(coordinates) {
+        if !self.obstacles.contains(&coordinates) && self.rovers.insert(coordinates) {
+            Ok(())
+        } else {
+            Err(DropError)
+        }
+    }
+
+    pub fn place_obstacle(&mut self, coordinates: rover::Coordinates) -> Result<(), DropError> {
+        if !self.rovers.contains(&coordinates) && self.obstacles.insert(coordinates) {
             Ok(())
         } else {
             Err(DropError)
         }
     }
 
-    fn is_move_inbounds(&self, coordinates: &rover::Coordinates) -> bool {
+    pub fn is_occupied(&self, coordinates: &rover::Coordinates) -> bool {
+        self.rovers.contains(coordinates) || self.obstacles.contains(coordinates)
+    }
+
+    pub fn is_inbounds(&self, coordinates: &rover::Coordinates) -> bool {
         if coordinates.x_coordinate > self.max_x_coordinate
             || coordinates.y_coordinate > self.max_y_coordinate
         {
@@ -43,7 +71,7 @@ impl Plateau {
 
     fn is_move_valid(&self, coordinates: &rover::Coordinates) -> Result<(), Box<dyn Error>> {
         self.can_rover_move(coordinates)?;
-        if !self.is_move_inbounds(coordinates) {
+        if !self.is_inbounds(coordinates) {
             Err(Box::new(OutOfBounds))
         } else {
             Ok(())
@@ -51,12 +79,27 @@ impl Plateau {
     }
 
     pub fn can_rover_move(&self, coordinates: &rover::Coordinates) -> Result<(), CollisionError> {
-        if !self.rovers.contains(coordinates) {
+        if !self.is_occupied(coordinates) {
             return Ok(());
         }
         Err(CollisionError)
     }
 
+    /// Rovers are drawn as `R`, obstacles as `#` and empty cells as `.`.
+    pub fn render(&self) -> String {
+        render_square(self, |c| self.symbol(c))
+    }
+
+    pub(crate) fn symbol(&self, coordinates: &rover::Coordinates) -> char {
+        if self.rovers.contains(coordinates) {
+            'R'
+        } else if self.obstacles.contains(coordinates) {
+            '#'
+        } else {
+            '.'
+        }
+    }
+
     pub fn update_rover_position(
         &mut self,
         old_coordinates: &rover::Coordinates,
@@ -66,12 +109,95 @@ impl Plateau {
         if !self.rovers.remove(old_coordinates) {
             Err(Box::new(NotFound))
         } else {
-            self.rovers.insert(new_coordinates.clone());
+            self.rovers.insert(*new_coordinates);
             Ok(())
         }
     }
 }
 
+impl Terrain for Plateau {
+    fn is_inbounds(&self, coordinates: &rover::Coordinates) -> bool {
+        Plateau::is_inbounds(self, coordinates)
+    }
+
+    fn is_occupied(&self, coordinates: &rover::Coordinates) -> bool {
+        Plateau::is_occupied(self, coordinates)
+    }
+
+    fn has_obstacle(&self, coordinates: &rover::Coordinates) -> bool {
+        self.obstacles.contains(coordinates)
+    }
+
+    fn cells(&self) -> Vec<rover::Coordinates> {
+        let mut cells = vec![];
+        for y_coordinate in 0..=self.max_y_coordinate {
+            for x_coordinate in 0..=self.max_x_coordinate {
+                cells.push(rover::Coordinates::new(x_coordinate, y_coordinate));
+            }
+        }
+        cells
+    }
+
+    fn drop_rover(&mut self, coordinates: rover::Coordinates) -> Result<(), Box<dyn Error>> {
+        if !self.is_inbounds(&coordinates) {
+            return Err(Box::new(OutOfBounds));
+        }
+        Ok(Plateau::drop_rover(self, coordinates)?)
+    }
+
+    fn place_obstacle(&mut self, coordinates: rover::Coordinates) -> Result<(), DropError> {
+        Plateau::place_obstacle(self, coordinates)
+    }
+
+    fn can_rover_move(&self, coordinates: &rover::Coordinates) -> Result<(), CollisionError> {
+        Plateau::can_rover_move(self, coordinates)
+    }
+
+    fn update_rover_position(
+        &mut self,
+        old_coordinates: &rover::Coordinates,
+        new_coordinates: &rover::Coordinates,
+    ) -> Result<(), Box<dyn Error>> {
+        Plateau::update_rover_position(self, old_coordinates, new_coordinates)
+    }
+
+    fn render(&self) -> String {
+        Plateau::render(self)
+    }
+
+    /// Looks through the rovers and obstacles rather than every cell in the box.
+    fn is_clear(
+        &self,
+        min: &rover::Coordinates,
+        max: &rover::Coordinates,
+        except: &rover::Coordinates,
+    ) -> bool {
+        Plateau::is_inbounds(self, max)
+            && !self
+                .rovers
+                .iter()
+                .chain(self.obstacles.iter())
+                .any(|c| c != except && terrain::within(c, min, max))
+    }
+}
+
+/// Draws a square plateau top row first, one character per cell separated by spaces.
+pub(crate) fn render_square(
+    plateau: &Plateau,
+    symbol: impl Fn(&rover::Coordinates) -> char,
+) -> String {
+    let mut lines = vec![];
+    for y_coordinate in (0..=plateau.max_y_coordinate).rev() {
+        let line: Vec<String> = (0..=plateau.max_x_coordinate)
+            .map(|x_coordinate| {
+                symbol(&rover::Coordinates::new(x_coordinate, y_coordinate)).to_string()
+            })
+            .collect();
+        lines.push(line.join(" "));
+    }
+    lines.join("\n")
+}
+
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub struct DropError;
 
@@ -169,6 +295,20 @@ mod tests {
         assert_eq!(vec![coordinates], plateau.list_rovers());
     }
 
+    #[test]
+    fn drop_rover_on_obstacle() {
+        let mut plateau = Plateau::new(5, 5);
+
+        let coordinates = rover::Coordinates::new(2, 2);
+
+        if let Err(e) = plateau.place_obstacle(coordinates) {
+            panic!("should be able to place obstacle : {}", e)
+        };
+
+        assert_eq!(Err(DropError), plateau.drop_rover(coordinates));
+        assert!(plateau.list_rovers().is_empty());
+    }
+
     #[test]
     fn rover_can_move() {
         let plateau = Plateau::new(5, 5);
@@ -230,12 +370,66 @@ mod tests {
             panic!("should be able to drop rover : {}", e)
         };
 
-        match plateau.update_rover_position(&old_coordinates, &new_coordinates) {
-            Ok(()) => panic!("should have returned collision error"),
-            _ => {}
+        if let Ok(()) = plateau.update_rover_position(&old_coordinates, &new_coordinates) {
+            panic!("should have returned collision error")
         }
     }
 
+    #[test]
+    fn move_rover_into_obstacle() {
+        let mut plateau = Plateau::new(5, 5);
+
+        let old_coordinates = rover::Coordinates::new(1, 1);
+        let new_coordinates = rover::Coordinates::new(1, 2);
+
+        if let Err(e) = plateau.place_obstacle(new_coordinates) {
+            panic!("should be able to place obstacle : {}", e)
+        };
+
+        if let Err(e) = plateau.drop_rover(old_coordinates) {
+            panic!("should be able to drop rover : {}", e)
+        };
+
+        assert_eq!(
+            Err(CollisionError),
+            plateau.can_rover_move(&new_coordinates)
+        );
+    }
+
+    #[test]
+    fn rover_cannot_squeeze_between_obstacles() {
+        let mut plateau = Plateau::new(5, 5);
+
+        let from = rover::Coordinates::new(1, 1);
+        let to = rover::Coordinates::new(2, 2);
+
+        if let Err(e) = plateau.place_obstacle(rover::Coordinates::new(2, 1)) {
+            panic!("should be able to place obstacle : {}", e)
+        };
+
+        assert_eq!(Ok(()), plateau.can_rover_squeeze(&from, &to));
+
+        if let Err(e) = plateau.drop_rover(rover::Coordinates::new(1, 2)) {
+            panic!("should be able to drop rover : {}", e)
+        };
+
+        assert_eq!(Err(CollisionError), plateau.can_rover_squeeze(&from, &to));
+    }
+
+    #[test]
+    fn render_plateau() {
+        let mut plateau = Plateau::new(2, 1);
+
+        if let Err(e) = plateau.drop_rover(rover::Coordinates::new(0, 0)) {
+            panic!("should be able to drop rover : {}", e)
+        };
+        if let Err(e) = plateau.place_obstacle(rover::Coordinates::new(2, 1)) {
+            panic!("should be able to place obstacle : {}", e)
+        };
+
+        assert_eq!(". . #\nR . .", plateau.render());
+    }
+
     #[test]
     fn move_rover_not_found() {
         let mut plateau = Plateau::new(5, 5);
@@ -243,9 +437,8 @@ mod tests {
         let old_coordinates = rover::Coordinates::new(1, 1);
         let new_coordinates = rover::Coordinates::new(0, 1);
 
-        match plateau.update_rover_position(&old_coordinates, &new_coordinates) {
-            Ok(()) => panic!("should not be able to update position"),
-            _ => {}
+        if let Ok(()) = plateau.update_rover_position(&old_coordinates, &new_coordinates) {
+            panic!("should not be able to update position")
         }
     }
 
@@ -260,9 +453,8 @@ mod tests {
             panic!("should be able to drop rover : {}", e)
         };
 
-        match plateau.update_rover_position(&old_coordinates, &new_coordinates) {
-            Ok(()) => panic!("should have returned out of bounds"),
-            _ => {}
+        if let Ok(()) = plateau.update_rover_position(&old_coordinates, &new_coordinates) {
+            panic!("should have returned out of bounds")
         }
     }
 
@@ -277,9 +469,8 @@ mod tests {
             panic!("should be able to drop rover : {}", e)
         };
 
-        match plateau.update_rover_position(&old_coordinates, &new_coordinates) {
-            Ok(()) => panic!("should have returned out of bounds"),
-            _ => {}
+        if let Ok(()) = plateau.update_rover_position(&old_coordinates, &new_coordinates) {
+            panic!("should have returned out of bounds")
         }
     }
 }
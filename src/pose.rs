@@ -0,0 +1,222 @@
+use crate::program::Instruction;
+use crate::rover::{Bearing, Coordinates};
+use crate::Command;
+
+/// Where a transform leaves a rover that started on one particular bearing, relative to
+/// where it started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Leg {
+    offset: (i64, i64),
+    bearing: Bearing,
+    /// Opposite corners of the box around every cell visited on the way.
+    min: (i64, i64),
+    max: (i64, i64),
+}
+
+/// The net effect of a run of commands on a rover's pose, worked out for every starting
+/// bearing so that transforms compose without replaying the commands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoseTransform {
+    legs: [Leg; 8],
+    steps: u64,
+}
+
+impl PoseTransform {
+    pub fn identity() -> PoseTransform {
+        PoseTransform::from_fn(0, |bearing| ((0, 0), bearing))
+    }
+
+    pub fn of_command(command: &Command) -> PoseTransform {
+        PoseTransform::from_fn(1, |bearing| {
+            let (dx, dy) = bearing.direction();
+            match command {
+                Command::MoveForward => ((dx, dy), bearing),
+                Command::MoveBackward => ((-dx, -dy), bearing),
+                Command::RightTurn => ((0, 0), bearing.turned(2)),
+                Command::LeftTurn => ((0, 0), bearing.turned(-2)),
+                Command::HalfRightTurn => ((0, 0), bearing.turned(1)),
+                Command::HalfLeftTurn => ((0, 0), bearing.turned(-1)),
+                Command::TakePhoto => ((0, 0), bearing),
+            }
+        })
+    }
+
+    /// The transform of a program, or `None` if it depends on sensors or calls macros, or
+    /// if it goes further than the arithmetic can follow.
+    pub fn of_program(program: &[Instruction]) -> Option<PoseTransform> {
+        let mut transform = PoseTransform::identity();
+        for instruction in program {
+            let next = match instruction {
+                Instruction::Command(command) => PoseTransform::of_command(command),
+                Instruction::Repeat(body, count) => {
+                    let start = PoseTransform::from_fn(1, |bearing| ((0, 0), bearing));
+                    start.then(&PoseTransform::of_program(body)?.repeated(*count)?)?
+                }
+                _ => return None,
+            };
+            transform = transform.then(&next)?;
+        }
+        Some(transform)
+    }
+
+    fn from_fn(steps: u64, leg: impl Fn(Bearing) -> ((i64, i64), Bearing)) -> PoseTransform {
+        let mut legs = [Leg {
+            offset: (0, 0),
+            bearing: Bearing::North,
+            min: (0, 0),
+            max: (0, 0),
+        }; 8];
+        for (index, bearing) in Bearing::ALL.iter().enumerate() {
+            let (offset, bearing) = leg(*bearing);
+            legs[index] = Leg {
+                offset,
+                bearing,
+                min: (offset.0.min(0), offset.1.min(0)),
+                max: (offset.0.max(0), offset.1.max(0)),
+            };
+        }
+        PoseTransform { legs, steps }
+    }
+
+    /// This transform followed by `next`.
+    pub fn then(&self, next: &PoseTransform) -> Option<PoseTransform> {
+        let mut legs = self.legs;
+        for leg in legs.iter_mut() {
+            let after = next.legs[leg.bearing as usize];
+            let shift = |(x, y): (i64, i64)| {
+                Some((leg.offset.0.checked_add(x)?, leg.offset.1.checked_add(y)?))
+            };
+            let (min, max) = (shift(after.min)?, shift(after.max)?);
+            *leg = Leg {
+                offset: shift(after.offset)?,
+                bearing: after.bearing,
+                min: (leg.min.0.min(min.0), leg.min.1.min(min.1)),
+                max: (leg.max.0.max(max.0), leg.max.1.max(max.1)),
+            };
+        }
+        Some(PoseTransform {
+            legs,
+            steps: self.steps.checked_add(next.steps)?,
+        })
+    }
+
+    /// This transform applied `count` times in a row, composed by repeated squaring.
+    pub fn repeated(&self, count: u64) -> Option<PoseTransform> {
+        let mut result = PoseTransform::identity();
+        let mut power = self.clone();
+        let mut count = count;
+        while count > 0 {
+            if count & 1 == 1 {
+                result = result.then(&power)?;
+            }
+            count >>= 1;
+            if count > 0 {
+                power = power.then(&power)?;
+            }
+        }
+        Some(result)
+    }
+
+    /// How much of the rover's instruction budget the commands use.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The pose the rover ends in, or `None` if it would step below zero or past
+    /// `u64::MAX` on the way there.
+    pub fn apply(
+        &self,
+        coordinates: Coordinates,
+        bearing: Bearing,
+    ) -> Option<(Coordinates, Bearing)> {
+        let leg = self.legs[bearing as usize];
+        self.bounds(coordinates, bearing)?;
+        Some((coordinates.step(leg.offset.0, leg.offset.1)?, leg.bearing))
+    }
+
+    /// Opposite corners of the box around every cell the rover visits on the way, or
+    /// `None` if any of them lies below zero or past `u64::MAX`.
+    pub fn bounds(
+        &self,
+        coordinates: Coordinates,
+        bearing: Bearing,
+    ) -> Option<(Coordinates, Coordinates)> {
+        let leg = self.legs[bearing as usize];
+        Some((
+            coordinates.step(leg.min.0, leg.min.1)?,
+            coordinates.step(leg.max.0, leg.max.1)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::parse;
+    use crate::rover::Rover;
+
+    fn transform(source: &str) -> PoseTransform {
+        PoseTransform::of_program(&parse(source).expect("should parse")).expect("closed form")
+    }
+
+    #[test]
+    fn compose_matches_stepping() {
+        let programs = ["MMRMMRMRRM", "M>MM<B", "RRRR", "(M>)7B3", "((ML)2 R)3"];
+        for source in programs.iter() {
+            let transform = transform(source);
+            for bearing in Bearing::ALL.iter() {
+                let mut rover = Rover::new(100, 100, *bearing);
+                for command in crate::program::expand(&parse(source).unwrap()).unwrap() {
+                    rover.execute_command(&command);
+                }
+                let (coordinates, bearing) = transform
+                    .apply(Coordinates::new(100, 100), *bearing)
+                    .expect("should stay on the grid");
+                assert_eq!(
+                    rover,
+                    Rover::new(coordinates.x_coordinate, coordinates.y_coordinate, bearing),
+                    "program {}",
+                    source
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bound_visited_cells() {
+        let transform = transform("MMRRMMMM");
+
+        assert_eq!(
+            Some((Coordinates::new(3, 1), Coordinates::new(3, 5))),
+            transform.bounds(Coordinates::new(3, 3), Bearing::North)
+        );
+        assert_eq!(
+            None,
+            transform.bounds(Coordinates::new(3, 1), Bearing::North)
+        );
+    }
+
+    #[test]
+    fn count_steps_like_the_interpreter() {
+        assert_eq!(5, transform("MRM2").steps());
+        assert_eq!(1 + 3 * (1 + 2 * 2), transform("((ML)2)3").steps());
+    }
+
+    #[test]
+    fn repeat_huge_counts() {
+        let transform = transform("(MRMRMRMR)1000000 (M)1000000");
+
+        assert_eq!(
+            Some((Coordinates::new(0, 1_000_000), Bearing::North)),
+            transform.apply(Coordinates::new(0, 0), Bearing::North)
+        );
+        assert_eq!(2 + 8_000_000 + 1_000_000, transform.steps());
+    }
+
+    #[test]
+    fn reject_sensors() {
+        let program = parse("M while not edge { M }").unwrap();
+
+        assert_eq!(None, PoseTransform::of_program(&program));
+    }
+}
@@ -0,0 +1,864 @@
+use crate::{Command, ParseError};
+use core::fmt;
+use std::collections::HashMap;
+use std::error;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// The largest count that may follow a command or group.
+pub const MAX_COUNT: u64 = 1_000_000;
+
+/// The most commands a single rover program may expand to, and the default number of
+/// steps a program with a `while` loop may take when run.
+pub const MAX_COMMANDS: u64 = 50_000_000;
+
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Instruction {
+    Command(Command),
+    Repeat(Vec<Instruction>, u64),
+    Call(String),
+    If(Condition, Vec<Instruction>, Vec<Instruction>),
+    While(Condition, Vec<Instruction>),
+}
+
+/// What a rover can sense about the cell directly ahead of it.
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sensor {
+    /// The cell ahead holds a rover or an obstacle.
+    Blocked,
+    /// The cell ahead is off the plateau.
+    Edge,
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Condition {
+    pub sensor: Sensor,
+    pub negated: bool,
+}
+
+impl Condition {
+    pub fn holds(&self, sense: impl Fn(Sensor) -> bool) -> bool {
+        sense(self.sensor) != self.negated
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sensor = match self.sensor {
+            Sensor::Blocked => "blocked",
+            Sensor::Edge => "edge",
+        };
+        if self.negated {
+            write!(f, "not {}", sensor)
+        } else {
+            write!(f, "{}", sensor)
+        }
+    }
+}
+
+/// Parses a rover program. A count may follow any command, parenthesised group or
+/// `[name]` macro call, so `M3(RM)2` means `MMMRMRM`. Programs may also branch on what the
+/// rover senses ahead, with `if blocked { R } else { M }` and `while not edge { M }`.
+/// Whitespace is ignored.
+pub fn parse(source: &str) -> Result<Vec<Instruction>, ParseError> {
+    Parser::new(source).sequence(None)
+}
+
+/// Parses and expands a command string into the flat list of commands it stands for.
+pub fn parse_commands(source: &str) -> Result<Vec<Command>, ParseError> {
+    expand(&parse(source)?)
+}
+
+/// Flattens a program into its commands. Macro calls must already have been resolved, and
+/// programs that branch on sensors cannot be flattened.
+pub fn expand(program: &[Instruction]) -> Result<Vec<Command>, ParseError> {
+    let length = expanded_length(program)?;
+    if length > MAX_COMMANDS {
+        return Err(ParseError::new(&format!(
+            "program expands to {} commands, more than the limit of {}",
+            length, MAX_COMMANDS
+        )));
+    }
+    let mut commands = Vec::with_capacity(length as usize);
+    expand_into(program, &mut commands);
+    Ok(commands)
+}
+
+fn expanded_length(program: &[Instruction]) -> Result<u64, ParseError> {
+    let mut length = 0u64;
+    for instruction in program {
+        let next = match instruction {
+            Instruction::Command(_) => 1,
+            Instruction::Repeat(body, count) => expanded_length(body)?.saturating_mul(*count),
+            Instruction::Call(name) => {
+                return Err(ParseError::new(&format!("undefined macro '{}'", name)))
+            }
+            Instruction::If(..) | Instruction::While(..) => {
+                return Err(ParseError::new(
+                    "program depends on sensors and cannot be flattened",
+                ))
+            }
+        };
+        length = length.saturating_add(next);
+    }
+    Ok(length)
+}
+
+fn expand_into(program: &[Instruction], commands: &mut Vec<Command>) {
+    for instruction in program {
+        if let Instruction::Repeat(body, count) = instruction {
+            for _ in 0..*count {
+                expand_into(body, commands);
+            }
+        } else if let Instruction::Command(command) = instruction {
+            commands.push(*command);
+        }
+    }
+}
+
+/// Whether the program has a `while` loop anywhere. Programs without one always finish.
+pub fn loops(program: &[Instruction]) -> bool {
+    program.iter().any(|instruction| match instruction {
+        Instruction::While(..) => true,
+        Instruction::Repeat(body, _) => loops(body),
+        Instruction::If(_, then, otherwise) => loops(then) || loops(otherwise),
+        Instruction::Command(_) | Instruction::Call(_) => false,
+    })
+}
+
+/// How many commands the program carries out, counting a repeated group's body once per
+/// repetition. The bodies of loops and both branches of conditions count once each, as
+/// their lengths depend on what the rover senses.
+pub fn executed(program: &[Instruction]) -> u64 {
+    program
+        .iter()
+        .map(|instruction| match instruction {
+            Instruction::Command(_) => 1,
+            Instruction::Repeat(body, count) => executed(body).saturating_mul(*count),
+            Instruction::If(_, then, otherwise) => {
+                executed(then).saturating_add(executed(otherwise))
+            }
+            Instruction::While(_, body) => executed(body),
+            Instruction::Call(_) => 0,
+        })
+        .fold(0, u64::saturating_add)
+}
+
+/// Every command written anywhere in the program, whether or not it would run.
+pub fn commands(program: &[Instruction]) -> Vec<&Command> {
+    let mut commands = vec![];
+    for instruction in program {
+        match instruction {
+            Instruction::Command(command) => commands.push(command),
+            Instruction::Repeat(body, _) | Instruction::While(_, body) => {
+                commands.extend(self::commands(body))
+            }
+            Instruction::If(_, then, otherwise) => {
+                commands.extend(self::commands(then));
+                commands.extend(self::commands(otherwise));
+            }
+            Instruction::Call(_) => {}
+        }
+    }
+    commands
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Parser<'a> {
+        Parser {
+            source,
+            chars: source.char_indices().peekable(),
+        }
+    }
+
+    /// Parses instructions up to the end of input, or up to the bracket closing the one
+    /// opened at the given column.
+    fn sequence(&mut self, closing: Option<(char, usize)>) -> Result<Vec<Instruction>, ParseError> {
+        let mut program = vec![];
+
+        while let Some((position, c)) = self.chars.next() {
+            let instruction = match c {
+                c if c.is_whitespace() => continue,
+                '(' => Instruction::Repeat(self.sequence(Some((')', position)))?, 1),
+                '[' => Instruction::Call(self.name(position)?),
+                c if closing.map(|(close, _)| close) == Some(c) => return Ok(program),
+                ')' | '}' => {
+                    return Err(ParseError::new(&format!(
+                        "unmatched '{}' at column {}",
+                        c,
+                        position + 1
+                    )))
+                }
+                c if c.is_ascii_digit() => {
+                    return Err(ParseError::new(&format!(
+                        "count at column {} does not follow a command or group",
+                        position + 1
+                    )))
+                }
+                _ if self.is_keyword(position, "if") => self.branch(position)?,
+                _ if self.is_keyword(position, "while") => self.repeat_while(position)?,
+                c => Instruction::Command(Command::new(c).map_err(|_| {
+                    ParseError::new(&format!(
+                        "unknown command '{}' at column {}",
+                        c,
+                        position + 1
+                    ))
+                })?),
+            };
+
+            let instruction = match self.count()? {
+                None => instruction,
+                Some(count) => match instruction {
+                    Instruction::Repeat(body, 1) => Instruction::Repeat(body, count),
+                    instruction => Instruction::Repeat(vec![instruction], count),
+                },
+            };
+            program.push(instruction);
+        }
+
+        match closing {
+            Some((_, position)) => Err(ParseError::new(&format!(
+                "unclosed '{}' at column {}",
+                &self.source[position..=position],
+                position + 1
+            ))),
+            None => Ok(program),
+        }
+    }
+
+    /// Whether the word starting at `position` is `keyword`, consuming it if so. The first
+    /// character has already been read.
+    fn is_keyword(&mut self, position: usize, keyword: &str) -> bool {
+        let is_keyword = self.is_word(position, keyword);
+        if is_keyword {
+            for _ in 1..keyword.len() {
+                self.chars.next();
+            }
+        }
+        is_keyword
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    /// Consumes `word` if it comes next and is not just the start of a longer word.
+    fn take_word(&mut self, word: &str) -> bool {
+        match self.chars.peek().map(|(position, _)| *position) {
+            Some(position) if self.is_word(position, word) => {
+                for _ in 0..word.len() {
+                    self.chars.next();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_word(&self, position: usize, word: &str) -> bool {
+        let rest = &self.source[position..];
+        rest.starts_with(word)
+            && !rest[word.len()..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    fn branch(&mut self, position: usize) -> Result<Instruction, ParseError> {
+        let condition = self.condition(position)?;
+        let then = self.block("if", position)?;
+        self.skip_whitespace();
+        let otherwise = if self.take_word("else") {
+            self.block("else", position)?
+        } else {
+            vec![]
+        };
+        Ok(Instruction::If(condition, then, otherwise))
+    }
+
+    fn repeat_while(&mut self, position: usize) -> Result<Instruction, ParseError> {
+        let condition = self.condition(position)?;
+        let body = self.block("while", position)?;
+        if body.is_empty() {
+            return Err(ParseError::new(&format!(
+                "while at column {} has an empty body",
+                position + 1
+            )));
+        }
+        Ok(Instruction::While(condition, body))
+    }
+
+    fn condition(&mut self, position: usize) -> Result<Condition, ParseError> {
+        self.skip_whitespace();
+        let negated = self.chars.next_if(|(_, c)| *c == '!').is_some() || self.take_word("not");
+        self.skip_whitespace();
+        let sensor = if self.take_word("blocked") {
+            Sensor::Blocked
+        } else if self.take_word("edge") {
+            Sensor::Edge
+        } else {
+            return Err(ParseError::new(&format!(
+                "expected 'blocked' or 'edge' after column {}",
+                position + 1
+            )));
+        };
+        Ok(Condition { sensor, negated })
+    }
+
+    fn block(&mut self, keyword: &str, position: usize) -> Result<Vec<Instruction>, ParseError> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some((open, '{')) => self.sequence(Some(('}', open))),
+            _ => Err(ParseError::new(&format!(
+                "expected '{{' after {} at column {}",
+                keyword,
+                position + 1
+            ))),
+        }
+    }
+
+    fn name(&mut self, opened_at: usize) -> Result<String, ParseError> {
+        let mut name = String::new();
+        for (position, c) in &mut self.chars {
+            match c {
+                ']' if is_valid_name(&name) => return Ok(name),
+                ']' => {
+                    return Err(ParseError::new(&format!(
+                        "invalid macro name '{}' at column {}",
+                        name,
+                        opened_at + 2
+                    )))
+                }
+                c if c.is_whitespace() => {
+                    return Err(ParseError::new(&format!(
+                        "unexpected space in macro name at column {}",
+                        position + 1
+                    )))
+                }
+                c => name.push(c),
+            }
+        }
+        Err(ParseError::new(&format!(
+            "unclosed '[' at column {}",
+            opened_at + 1
+        )))
+    }
+
+    fn count(&mut self) -> Result<Option<u64>, ParseError> {
+        let start = match self.chars.peek() {
+            Some((position, c)) if c.is_ascii_digit() => *position,
+            _ => return Ok(None),
+        };
+
+        let mut digits = String::new();
+        while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+            digits.push(c);
+        }
+
+        match digits.parse::<u64>() {
+            Ok(0) => Err(ParseError::new(&format!(
+                "count at column {} must be at least 1",
+                start + 1
+            ))),
+            Ok(count) if count <= MAX_COUNT => Ok(Some(count)),
+            _ => Err(ParseError::new(&format!(
+                "count {} at column {} is larger than the limit of {}",
+                digits,
+                start + 1,
+                MAX_COUNT
+            ))),
+        }
+    }
+}
+
+/// Macro names start with a letter or underscore, followed by letters, digits or underscores.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Runs a program one command at a time, evaluating conditions against the rover's
+/// sensors as it reaches them. Every instruction reached, including each check of a loop
+/// condition, counts as a step, and running out of steps stops the program so loops
+/// always terminate.
+pub struct Interpreter<'a> {
+    stack: Vec<Frame<'a>>,
+    steps_left: u64,
+}
+
+enum Frame<'a> {
+    Block {
+        body: &'a [Instruction],
+        index: usize,
+        repeats_left: u64,
+    },
+    While {
+        condition: &'a Condition,
+        body: &'a [Instruction],
+        index: usize,
+    },
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a [Instruction], budget: u64) -> Interpreter<'a> {
+        Interpreter {
+            stack: vec![Frame::Block {
+                body: program,
+                index: 0,
+                repeats_left: 1,
+            }],
+            steps_left: budget,
+        }
+    }
+
+    /// The next command to execute, or `None` once the program has finished.
+    pub fn next_command(
+        &mut self,
+        sense: impl Fn(Sensor) -> bool,
+    ) -> Result<Option<Command>, BudgetExceeded> {
+        let Interpreter { stack, steps_left } = self;
+        loop {
+            let instruction = match stack.last_mut() {
+                None => return Ok(None),
+                Some(Frame::Block {
+                    body,
+                    index,
+                    repeats_left,
+                }) => {
+                    if *index == body.len() {
+                        if body.is_empty() || *repeats_left <= 1 {
+                            stack.pop();
+                            continue;
+                        }
+                        *index = 0;
+                        *repeats_left -= 1;
+                    }
+                    *index += 1;
+                    &body[*index - 1]
+                }
+                Some(Frame::While {
+                    condition,
+                    body,
+                    index,
+                }) => {
+                    if *index == body.len() {
+                        *index = 0;
+                    }
+                    if *index == 0 {
+                        step(steps_left)?;
+                        if !condition.holds(&sense) {
+                            stack.pop();
+                            continue;
+                        }
+                    }
+                    *index += 1;
+                    &body[*index - 1]
+                }
+            };
+
+            step(steps_left)?;
+            match instruction {
+                Instruction::Command(command) => return Ok(Some(*command)),
+                Instruction::Repeat(body, count) => stack.push(Frame::Block {
+                    body,
+                    index: 0,
+                    repeats_left: *count,
+                }),
+                Instruction::If(condition, then, otherwise) => stack.push(Frame::Block {
+                    body: if condition.holds(&sense) {
+                        then
+                    } else {
+                        otherwise
+                    },
+                    index: 0,
+                    repeats_left: 1,
+                }),
+                Instruction::While(condition, body) => stack.push(Frame::While {
+                    condition,
+                    body,
+                    index: 0,
+                }),
+                Instruction::Call(_) => {}
+            }
+        }
+    }
+}
+
+fn step(steps_left: &mut u64) -> Result<(), BudgetExceeded> {
+    if *steps_left == 0 {
+        return Err(BudgetExceeded);
+    }
+    *steps_left -= 1;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialOrd, PartialEq)]
+pub struct BudgetExceeded;
+
+impl error::Error for BudgetExceeded {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rover program ran out of instruction budget")
+    }
+}
+
+/// Named command sequences a mission can call from its rover programs with `[name]`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Macros {
+    definitions: HashMap<String, Definition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Definition {
+    program: Vec<Instruction>,
+    line: usize,
+}
+
+impl Macros {
+    pub fn new() -> Macros {
+        Macros::default()
+    }
+
+    /// Defines a macro from its source. `line` is where the definition appears, so errors
+    /// in the body or in calls to it can point back there.
+    pub fn define(&mut self, name: &str, source: &str, line: usize) -> Result<(), ParseError> {
+        if !is_valid_name(name) {
+            return Err(ParseError::new(&format!(
+                "invalid macro name '{}' at line {}",
+                name, line
+            )));
+        }
+        if let Some(existing) = self.definitions.get(name) {
+            return Err(ParseError::new(&format!(
+                "macro '{}' at line {} is already defined at line {}",
+                name, line, existing.line
+            )));
+        }
+        let program = parse(source).map_err(|e| {
+            ParseError::new(&format!("in macro '{}' at line {}: {}", name, line, e))
+        })?;
+        self.definitions
+            .insert(name.to_string(), Definition { program, line });
+        Ok(())
+    }
+
+    /// Replaces every macro call in the program with the macro's body.
+    pub fn resolve(&self, program: &[Instruction]) -> Result<Vec<Instruction>, ParseError> {
+        self.resolve_within(program, &mut vec![])
+    }
+
+    fn resolve_within<'a>(
+        &'a self,
+        program: &[Instruction],
+        calling: &mut Vec<&'a str>,
+    ) -> Result<Vec<Instruction>, ParseError> {
+        let mut resolved = vec![];
+        for instruction in program {
+            resolved.push(match instruction {
+                Instruction::Command(command) => Instruction::Command(*command),
+                Instruction::Repeat(body, count) => {
+                    Instruction::Repeat(self.resolve_within(body, calling)?, *count)
+                }
+                Instruction::If(condition, then, otherwise) => Instruction::If(
+                    *condition,
+                    self.resolve_within(then, calling)?,
+                    self.resolve_within(otherwise, calling)?,
+                ),
+                Instruction::While(condition, body) => {
+                    Instruction::While(*condition, self.resolve_within(body, calling)?)
+                }
+                Instruction::Call(name) => {
+                    let (name, definition) = self
+                        .definitions
+                        .get_key_value(name.as_str())
+                        .ok_or_else(|| ParseError::new(&format!("undefined macro '{}'", name)))?;
+                    if let Some(start) = calling.iter().position(|c| c == name) {
+                        let mut cycle = calling[start..].to_vec();
+                        cycle.push(name);
+                        return Err(ParseError::new(&format!(
+                            "macro '{}' at line {} calls itself through {}",
+                            name,
+                            definition.line,
+                            cycle.join(" -> ")
+                        )));
+                    }
+                    calling.push(name);
+                    let body = self.resolve_within(&definition.program, calling)?;
+                    calling.pop();
+                    Instruction::Repeat(body, 1)
+                }
+            });
+        }
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plain_commands() {
+        assert_eq!(
+            Ok(vec![
+                Command::MoveForward,
+                Command::RightTurn,
+                Command::LeftTurn
+            ]),
+            parse_commands("MRL")
+        );
+    }
+
+    #[test]
+    fn parse_counts() {
+        let commands = parse_commands("M3R M2").expect("should parse");
+
+        assert_eq!(
+            vec![
+                Command::MoveForward,
+                Command::MoveForward,
+                Command::MoveForward,
+                Command::RightTurn,
+                Command::MoveForward,
+                Command::MoveForward,
+            ],
+            commands
+        );
+    }
+
+    #[test]
+    fn parse_nested_groups() {
+        let program = parse("(M(R)2)3").expect("should parse");
+
+        assert_eq!(
+            vec![Instruction::Repeat(
+                vec![
+                    Instruction::Command(Command::MoveForward),
+                    Instruction::Repeat(vec![Instruction::Command(Command::RightTurn)], 2),
+                ],
+                3
+            )],
+            program
+        );
+        assert_eq!(Ok(9), expand(&program).map(|c| c.len()));
+    }
+
+    #[test]
+    fn reject_unbalanced_parentheses() {
+        assert_eq!(
+            Err(ParseError::new("unclosed '(' at column 2")),
+            parse("M(MR")
+        );
+        assert_eq!(
+            Err(ParseError::new("unmatched ')' at column 3")),
+            parse("MR)")
+        );
+    }
+
+    #[test]
+    fn reject_zero_count() {
+        assert_eq!(
+            Err(ParseError::new("count at column 2 must be at least 1")),
+            parse("M0")
+        );
+    }
+
+    #[test]
+    fn reject_oversized_count() {
+        assert!(parse("M1000001").is_err());
+        assert!(parse("M99999999999999999999999").is_err());
+    }
+
+    #[test]
+    fn reject_oversized_expansion() {
+        let program = parse("((M1000)1000)1000").expect("should parse");
+
+        assert!(expand(&program).is_err());
+    }
+
+    #[test]
+    fn reject_unknown_command() {
+        assert_eq!(
+            Err(ParseError::new("unknown command 'X' at column 2")),
+            parse("MX")
+        );
+    }
+
+    #[test]
+    fn reject_count_without_command() {
+        assert!(parse("3M").is_err());
+    }
+
+    #[test]
+    fn resolve_nested_macros() {
+        let mut macros = Macros::new();
+        macros.define("side", "M3R", 1).expect("should define side");
+        macros
+            .define("square", "[side]4", 2)
+            .expect("should define square");
+
+        let program = macros
+            .resolve(&parse("[square]L").expect("should parse"))
+            .expect("should resolve");
+
+        assert_eq!(Ok(17), expand(&program).map(|c| c.len()));
+    }
+
+    #[test]
+    fn reject_undefined_macro() {
+        let program = parse("[square]").expect("should parse");
+
+        assert_eq!(
+            Err(ParseError::new("undefined macro 'square'")),
+            Macros::new().resolve(&program)
+        );
+        assert!(expand(&program).is_err());
+    }
+
+    #[test]
+    fn reject_recursive_macros() {
+        let mut macros = Macros::new();
+        macros.define("a", "M[b]", 1).expect("should define a");
+        macros.define("b", "R[a]", 2).expect("should define b");
+
+        assert_eq!(
+            Err(ParseError::new(
+                "macro 'a' at line 1 calls itself through a -> b -> a"
+            )),
+            macros.resolve(&parse("[a]").expect("should parse"))
+        );
+    }
+
+    #[test]
+    fn reject_bad_macro_body() {
+        let mut macros = Macros::new();
+
+        assert_eq!(
+            Err(ParseError::new(
+                "in macro 'square' at line 3: unclosed '(' at column 1"
+            )),
+            macros.define("square", "(M3R4", 3)
+        );
+    }
+
+    #[test]
+    fn reject_duplicate_macro() {
+        let mut macros = Macros::new();
+        macros.define("a", "M", 1).expect("should define a");
+
+        assert!(macros.define("a", "R", 4).is_err());
+    }
+
+    #[test]
+    fn parse_conditionals() {
+        let program =
+            parse("if blocked { R } else { M2 } while not edge { M }").expect("should parse");
+
+        assert_eq!(
+            vec![
+                Instruction::If(
+                    Condition {
+                        sensor: Sensor::Blocked,
+                        negated: false
+                    },
+                    vec![Instruction::Command(Command::RightTurn)],
+                    vec![Instruction::Repeat(
+                        vec![Instruction::Command(Command::MoveForward)],
+                        2
+                    )],
+                ),
+                Instruction::While(
+                    Condition {
+                        sensor: Sensor::Edge,
+                        negated: true
+                    },
+                    vec![Instruction::Command(Command::MoveForward)],
+                ),
+            ],
+            program
+        );
+    }
+
+    #[test]
+    fn reject_bad_conditionals() {
+        assert!(parse("if { R }").is_err());
+        assert!(parse("if blocked R").is_err());
+        assert!(parse("if blocked { R").is_err());
+        assert!(parse("while !edge {}").is_err());
+        assert!(parse("iff blocked { R }").is_err());
+    }
+
+    #[test]
+    fn conditionals_cannot_be_flattened() {
+        let program = parse("if edge { R }").expect("should parse");
+
+        assert!(expand(&program).is_err());
+    }
+
+    fn run(program: &[Instruction], budget: u64, sense: impl Fn(Sensor) -> bool) -> Vec<Command> {
+        let mut interpreter = Interpreter::new(program, budget);
+        let mut commands = vec![];
+        while let Some(command) = interpreter
+            .next_command(&sense)
+            .expect("should stay within budget")
+        {
+            commands.push(command);
+        }
+        commands
+    }
+
+    #[test]
+    fn interpret_branches_on_sensor() {
+        let program = parse("if blocked { R } else { M } L").expect("should parse");
+
+        assert_eq!(
+            vec![Command::RightTurn, Command::LeftTurn],
+            run(&program, 10, |sensor| sensor == Sensor::Blocked)
+        );
+        assert_eq!(
+            vec![Command::MoveForward, Command::LeftTurn],
+            run(&program, 10, |_| false)
+        );
+    }
+
+    #[test]
+    fn interpret_repeats_like_expand() {
+        let program = parse("(M(RL)2)3 B").expect("should parse");
+
+        assert_eq!(
+            expand(&program).expect("should expand"),
+            run(&program, 100, |_| false)
+        );
+    }
+
+    #[test]
+    fn interpreter_stops_endless_loops() {
+        let program = parse("while not edge { R }").expect("should parse");
+        let mut interpreter = Interpreter::new(&program, 100);
+
+        let mut result = Ok(None);
+        for _ in 0..100 {
+            result = interpreter.next_command(|_| false);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        assert_eq!(Err(BudgetExceeded), result);
+    }
+}
@@ -0,0 +1,350 @@
+use crate::plateau::{OutOfBounds, Plateau};
+use crate::rover::{Bearing, Compass, Coordinates, Rover, Vehicle};
+use crate::{program, Command, ParseError};
+use std::error::Error;
+
+pub const HELP: &str = "\
+plateau <x> <y>        create an empty plateau, discarding any rovers
+obstacle <x> <y>       place an obstacle
+land <x> <y> <bearing> land a rover, named r1, r2 and so on
+<rover> <commands>     drive a rover, as in 'r1 MMRM'
+compass <four|eight>   choose which bearings and turns rovers may use
+status                 list every rover's position
+map                    draw the plateau
+undo                   take back the last change
+help                   print this message";
+
+const NO_PLATEAU: &str = "no plateau yet, create one with 'plateau x y'";
+
+/// The state an undo goes back to: the plateau and every rover's pose.
+struct Snapshot {
+    plateau: Plateau,
+    rovers: Vec<(Coordinates, Bearing)>,
+}
+
+/// An interactive session driving rovers one line at a time.
+#[derive(Default)]
+pub struct Session {
+    plateau: Option<Plateau>,
+    rovers: Vec<Rover>,
+    history: Vec<Snapshot>,
+    compass: Compass,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::default()
+    }
+
+    /// A session whose rovers start out following the given compass.
+    pub fn with_compass(compass: Compass) -> Session {
+        Session {
+            compass,
+            ..Session::default()
+        }
+    }
+
+    /// Carries out one line of input, returning what to show the operator. A rover that
+    /// fails part way through its commands stays where it stopped, and the error that
+    /// stopped it is returned.
+    pub fn execute(&mut self, line: &str) -> Result<String, Box<dyn Error>> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            [] => Ok(String::new()),
+            ["help"] => Ok(String::from(HELP)),
+            ["plateau", x, y] => {
+                self.plateau = Some(Plateau::new(number(x)?, number(y)?));
+                self.rovers.clear();
+                self.history.clear();
+                Ok(self.map())
+            }
+            ["obstacle", x, y] => {
+                let coordinates = Coordinates::new(number(x)?, number(y)?);
+                if !self.plateau()?.is_inbounds(&coordinates) {
+                    return Err(Box::new(OutOfBounds));
+                }
+                let snapshot = self.snapshot()?;
+                self.plateau_mut()?.place_obstacle(coordinates)?;
+                self.history.push(snapshot);
+                Ok(self.map())
+            }
+            ["land", x, y, bearing] => {
+                let coordinates = Coordinates::new(number(x)?, number(y)?);
+                let bearing: Bearing = bearing.parse().map_err(|e: String| ParseError::new(&e))?;
+                Rover::check_bearing(bearing, self.compass).map_err(|e| ParseError::new(&e))?;
+                if !self.plateau()?.is_inbounds(&coordinates) {
+                    return Err(Box::new(OutOfBounds));
+                }
+                let snapshot = self.snapshot()?;
+                self.plateau_mut()?.drop_rover(coordinates)?;
+                self.history.push(snapshot);
+                self.rovers.push(Rover::new(
+                    coordinates.x_coordinate,
+                    coordinates.y_coordinate,
+                    bearing,
+                ));
+                Ok(format!(
+                    "{}\nr{} {}",
+                    self.map(),
+                    self.rovers.len(),
+                    self.rovers[self.rovers.len() - 1]
+                ))
+            }
+            ["status"] => {
+                let status: Vec<String> = self
+                    .rovers
+                    .iter()
+                    .enumerate()
+                    .map(|(index, rover)| format!("r{} {}", index + 1, rover))
+                    .collect();
+                Ok(status.join("\n"))
+            }
+            ["compass", compass] => {
+                let compass = match compass {
+                    "four" => Compass::FourPoint,
+                    "eight" => Compass::EightPoint {
+                        allow_squeeze: false,
+                    },
+                    other => {
+                        let details = format!("unknown compass '{}', try four or eight", other);
+                        return Err(Box::new(ParseError::new(&details)));
+                    }
+                };
+                for rover in &self.rovers {
+                    Rover::check_bearing(rover.bearing(), compass)
+                        .map_err(|e| ParseError::new(&e))?;
+                }
+                self.compass = compass;
+                Ok(String::new())
+            }
+            ["map"] => {
+                self.plateau()?;
+                Ok(self.map())
+            }
+            ["undo"] => {
+                let snapshot = self
+                    .history
+                    .pop()
+                    .ok_or_else(|| ParseError::new("nothing to undo"))?;
+                self.plateau = Some(snapshot.plateau);
+                self.rovers = snapshot
+                    .rovers
+                    .into_iter()
+                    .map(|(coordinates, bearing)| {
+                        Rover::new(coordinates.x_coordinate, coordinates.y_coordinate, bearing)
+                    })
+                    .collect();
+                Ok(self.map())
+            }
+            [name, ..] if is_rover_name(name) => {
+                let index = name[1..]
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| (1..=self.rovers.len()).contains(index))
+                    .ok_or_else(|| ParseError::new(&format!("no rover named '{}'", name)))?;
+                let commands = program::parse_commands(&words[1..].concat())?;
+                for command in &commands {
+                    Rover::check_command(command, self.compass).map_err(|e| ParseError::new(&e))?;
+                }
+                let snapshot = self.snapshot()?;
+                self.history.push(snapshot);
+                self.drive(index - 1, &commands)?;
+                Ok(format!(
+                    "{}\n{} {}",
+                    self.map(),
+                    name,
+                    self.rovers[index - 1]
+                ))
+            }
+            _ => Err(Box::new(ParseError::new(&format!(
+                "unknown command '{}', try 'help'",
+                line.trim()
+            )))),
+        }
+    }
+
+    /// Draws the plateau, or nothing before one has been created.
+    pub fn map(&self) -> String {
+        match &self.plateau {
+            Some(plateau) => plateau.render(),
+            None => String::new(),
+        }
+    }
+
+    fn drive(&mut self, index: usize, commands: &[Command]) -> Result<(), Box<dyn Error>> {
+        let plateau = self
+            .plateau
+            .as_mut()
+            .ok_or_else(|| ParseError::new(NO_PLATEAU))?;
+        let rover = &mut self.rovers[index];
+        for command in commands {
+            crate::step(plateau, rover, command, self.compass)?;
+        }
+        Ok(())
+    }
+
+    fn plateau(&self) -> Result<&Plateau, ParseError> {
+        self.plateau
+            .as_ref()
+            .ok_or_else(|| ParseError::new(NO_PLATEAU))
+    }
+
+    fn plateau_mut(&mut self) -> Result<&mut Plateau, ParseError> {
+        self.plateau
+            .as_mut()
+            .ok_or_else(|| ParseError::new(NO_PLATEAU))
+    }
+
+    fn snapshot(&self) -> Result<Snapshot, ParseError> {
+        Ok(Snapshot {
+            plateau: self.plateau()?.clone(),
+            rovers: self
+                .rovers
+                .iter()
+                .map(|rover| (*rover.coordinates(), rover.bearing()))
+                .collect(),
+        })
+    }
+}
+
+/// Whether the word names a rover, as `r` followed by its number.
+fn is_rover_name(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('r') && word[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+fn number(s: &str) -> Result<u64, ParseError> {
+    s.parse()
+        .map_err(|_| ParseError::new(&format!("'{}' is not a whole number", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plateau::{CollisionError, DropError};
+
+    fn session(lines: &[&str]) -> Session {
+        let mut session = Session::new();
+        for line in lines {
+            session.execute(line).expect("should execute");
+        }
+        session
+    }
+
+    #[test]
+    fn drive_a_rover() {
+        let mut session = session(&["plateau 5 5", "land 1 2 N"]);
+
+        let output = session.execute("r1 LMLMLMLMM").expect("should drive");
+
+        assert!(output.ends_with("r1 1 3 N"));
+        assert_eq!("r1 1 3 N", session.execute("status").unwrap());
+    }
+
+    #[test]
+    fn stop_rovers_at_the_first_error() {
+        let mut session = session(&["plateau 5 5", "land 1 2 N", "land 1 4 S"]);
+
+        let error = session.execute("r1 MMM").expect_err("should collide");
+
+        assert!(error.is::<CollisionError>());
+        assert_eq!("r1 1 3 N\nr2 1 4 S", session.execute("status").unwrap());
+
+        let error = session
+            .execute("r2 RRMM")
+            .expect_err("should leave the plateau");
+
+        assert!(error.is::<OutOfBounds>());
+        assert_eq!("r1 1 3 N\nr2 1 5 N", session.execute("status").unwrap());
+    }
+
+    #[test]
+    fn reject_occupied_landing_cells() {
+        let mut session = session(&["plateau 5 5", "obstacle 2 2", "land 1 2 N"]);
+
+        assert!(session.execute("land 2 2 N").unwrap_err().is::<DropError>());
+        assert!(session.execute("land 1 2 E").unwrap_err().is::<DropError>());
+        assert!(session
+            .execute("land 6 2 E")
+            .unwrap_err()
+            .is::<OutOfBounds>());
+        assert_eq!("r1 1 2 N", session.execute("status").unwrap());
+    }
+
+    #[test]
+    fn undo_every_change() {
+        let mut session = session(&["plateau 2 2", "land 0 0 N", "r1 MR", "land 1 1 S"]);
+
+        session.execute("undo").expect("should undo landing");
+        assert_eq!("r1 0 1 E", session.execute("status").unwrap());
+
+        session.execute("undo").expect("should undo drive");
+        assert_eq!("r1 0 0 N", session.execute("status").unwrap());
+        assert_eq!(". . .\n. . .\nR . .", session.execute("map").unwrap());
+
+        session.execute("undo").expect("should undo landing");
+        assert_eq!("", session.execute("status").unwrap());
+        assert!(session.execute("undo").is_err());
+    }
+
+    #[test]
+    fn explain_bad_input() {
+        let mut session = Session::new();
+
+        assert_eq!(
+            "no plateau yet, create one with 'plateau x y'",
+            session.execute("land 1 2 N").unwrap_err().to_string()
+        );
+        session.execute("plateau 5 5").unwrap();
+        assert_eq!(
+            "no rover named 'r1'",
+            session.execute("r1 M").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "unknown command 'fly', try 'help'",
+            session.execute("fly").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn follow_the_sessions_compass() {
+        let mut session = session(&["plateau 5 5", "land 1 1 N"]);
+
+        assert!(session.execute("land 2 2 NE").is_err());
+        assert!(session.execute("r1 M>M").is_err());
+        assert_eq!("r1 1 1 N", session.execute("status").unwrap());
+
+        session
+            .execute("compass eight")
+            .expect("should change compass");
+        session.execute("land 2 2 NE").expect("should land");
+        session.execute("r1 M>M").expect("should drive");
+        assert_eq!("r1 2 3 NE\nr2 2 2 NE", session.execute("status").unwrap());
+
+        assert!(session.execute("compass four").is_err());
+        assert!(session.execute("compass six").is_err());
+    }
+
+    #[test]
+    fn start_on_the_compass_given() {
+        let mut session = Session::with_compass(Compass::EightPoint {
+            allow_squeeze: false,
+        });
+        session.execute("plateau 5 5").unwrap();
+
+        session.execute("land 1 1 NE").expect("should land");
+    }
+
+    #[test]
+    fn only_take_r_and_a_number_as_a_rover_name() {
+        let mut session = session(&["plateau 5 5", "land 1 1 N"]);
+
+        assert_eq!(
+            "unknown command 'reset', try 'help'",
+            session.execute("reset").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "unknown command 'r M', try 'help'",
+            session.execute("r M").unwrap_err().to_string()
+        );
+    }
+}
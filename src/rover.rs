@@ -1,11 +1,67 @@
+use crate::camera::Camera;
+use crate::pose::PoseTransform;
+use crate::program::Instruction;
 use crate::Command;
 use core::fmt;
 use std::str::FromStr;
 
+/// Anything that can be landed on a terrain and driven by commands. The engine asks a
+/// vehicle where a command would take it, checks that against the terrain, and only then
+/// moves it.
+pub trait Vehicle: fmt::Display + Sized {
+    /// How many turn steps make a full circle.
+    const HEADINGS: u64;
+
+    fn land(x_coordinate: u64, y_coordinate: u64, bearing: Bearing) -> Result<Self, String>;
+
+    /// Rejects bearings the vehicle cannot hold under the mission's compass.
+    fn check_bearing(bearing: Bearing, compass: Compass) -> Result<(), String>;
+
+    /// Rejects commands the vehicle cannot follow under the mission's compass.
+    fn check_command(command: &Command, compass: Compass) -> Result<(), String>;
+
+    /// How many turn steps clockwise the command turns the vehicle, or `None` if it is
+    /// not a turn.
+    fn turn_steps(command: &Command) -> Option<u64>;
+
+    fn coordinates(&self) -> &Coordinates;
+
+    fn bearing(&self) -> Bearing;
+
+    /// How far the command would move the vehicle along each axis, or `None` if it does
+    /// not move it. The terrain works out which cell the step leads to.
+    fn planned_step(&self, command: &Command) -> Option<(i64, i64)>;
+
+    fn execute_command(&mut self, command: &Command);
+
+    /// Places the vehicle on coordinates the terrain has already approved.
+    fn move_to(&mut self, coordinates: Coordinates);
+
+    /// Turns the vehicle to a bearing the engine has already worked out it reaches.
+    fn face(&mut self, bearing: Bearing);
+
+    /// The net effect of a run of commands, for vehicles whose moves compose into a
+    /// `PoseTransform`. The engine steps vehicles that return `None` one command at a
+    /// time.
+    fn transform(_program: &[Instruction]) -> Option<PoseTransform> {
+        None
+    }
+
+    fn camera(&self) -> Option<&Camera> {
+        None
+    }
+
+    fn fit_camera(&mut self, _camera: Camera) -> Result<(), String> {
+        Err(String::from("vehicle cannot carry a camera"))
+    }
+}
+
 #[derive(Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rover {
     bearing: Bearing,
     coordinates: Coordinates,
+    camera: Option<Camera>,
 }
 
 impl Rover {
@@ -16,79 +72,297 @@ impl Rover {
                 x_coordinate,
                 y_coordinate,
             },
+            camera: None,
         }
     }
 
-    pub fn planned_move(&self) -> Coordinates {
-        let mut planned_coordinates = self.coordinates;
-        planned_coordinates.move_forward(self.bearing);
-        planned_coordinates
+    pub fn with_camera(mut self, camera: Camera) -> Rover {
+        self.camera = Some(camera);
+        self
+    }
+
+    /// Where moving forward would take the rover, or `None` if it would step below zero or
+    /// past `u64::MAX`.
+    pub fn planned_move(&self) -> Option<Coordinates> {
+        let (dx, dy) = self.bearing.direction();
+        self.coordinates.step(dx, dy)
+    }
+
+    pub fn planned_move_backward(&self) -> Option<Coordinates> {
+        let (dx, dy) = self.bearing.direction();
+        self.coordinates.step(-dx, -dy)
     }
 
     pub fn coordinates(&self) -> &Coordinates {
         &self.coordinates
     }
 
+    pub fn bearing(&self) -> Bearing {
+        self.bearing
+    }
+
+    pub fn camera(&self) -> Option<&Camera> {
+        self.camera.as_ref()
+    }
+
+    pub(crate) fn face(&mut self, bearing: Bearing) {
+        self.bearing = bearing;
+    }
+
+    /// Carries out the command. A move that would step below zero or past `u64::MAX`
+    /// leaves the rover where it is.
     pub fn execute_command(&mut self, turn: &Command) {
-        match (self.bearing, turn) {
-            (Bearing::North, Command::RightTurn) => self.bearing = Bearing::East,
-            (Bearing::North, Command::LeftTurn) => self.bearing = Bearing::West,
-            (Bearing::East, Command::RightTurn) => self.bearing = Bearing::South,
-            (Bearing::East, Command::LeftTurn) => self.bearing = Bearing::North,
-            (Bearing::South, Command::RightTurn) => self.bearing = Bearing::West,
-            (Bearing::South, Command::LeftTurn) => self.bearing = Bearing::East,
-            (Bearing::West, Command::RightTurn) => self.bearing = Bearing::North,
-            (Bearing::West, Command::LeftTurn) => self.bearing = Bearing::South,
-            (_, Command::MoveForward) => self.coordinates.move_forward(self.bearing),
+        match turn {
+            Command::RightTurn => self.bearing = self.bearing.turned(2),
+            Command::LeftTurn => self.bearing = self.bearing.turned(-2),
+            Command::HalfRightTurn => self.bearing = self.bearing.turned(1),
+            Command::HalfLeftTurn => self.bearing = self.bearing.turned(-1),
+            Command::MoveForward => {
+                self.coordinates = self.planned_move().unwrap_or(self.coordinates)
+            }
+            Command::MoveBackward => {
+                self.coordinates = self.planned_move_backward().unwrap_or(self.coordinates)
+            }
+            Command::TakePhoto => {}
         };
     }
 }
 
+impl Vehicle for Rover {
+    const HEADINGS: u64 = 8;
+
+    fn land(x_coordinate: u64, y_coordinate: u64, bearing: Bearing) -> Result<Rover, String> {
+        Ok(Rover::new(x_coordinate, y_coordinate, bearing))
+    }
+
+    fn check_bearing(bearing: Bearing, compass: Compass) -> Result<(), String> {
+        if compass == Compass::FourPoint && bearing.is_diagonal() {
+            return Err(String::from(
+                "diagonal bearings need an eight point compass",
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_command(command: &Command, compass: Compass) -> Result<(), String> {
+        match (command, compass) {
+            (Command::HalfLeftTurn, Compass::FourPoint)
+            | (Command::HalfRightTurn, Compass::FourPoint) => {
+                Err(String::from("half turns need an eight point compass"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn turn_steps(command: &Command) -> Option<u64> {
+        match command {
+            Command::RightTurn => Some(2),
+            Command::LeftTurn => Some(6),
+            Command::HalfRightTurn => Some(1),
+            Command::HalfLeftTurn => Some(7),
+            _ => None,
+        }
+    }
+
+    fn coordinates(&self) -> &Coordinates {
+        Rover::coordinates(self)
+    }
+
+    fn bearing(&self) -> Bearing {
+        Rover::bearing(self)
+    }
+
+    fn planned_step(&self, command: &Command) -> Option<(i64, i64)> {
+        let (dx, dy) = self.bearing.direction();
+        match command {
+            Command::MoveForward => Some((dx, dy)),
+            Command::MoveBackward => Some((-dx, -dy)),
+            _ => None,
+        }
+    }
+
+    fn execute_command(&mut self, command: &Command) {
+        Rover::execute_command(self, command)
+    }
+
+    fn move_to(&mut self, coordinates: Coordinates) {
+        self.coordinates = coordinates;
+    }
+
+    fn face(&mut self, bearing: Bearing) {
+        Rover::face(self, bearing)
+    }
+
+    fn transform(program: &[Instruction]) -> Option<PoseTransform> {
+        PoseTransform::of_program(program)
+    }
+
+    fn camera(&self) -> Option<&Camera> {
+        Rover::camera(self)
+    }
+
+    fn fit_camera(&mut self, camera: Camera) -> Result<(), String> {
+        self.camera = Some(camera);
+        Ok(())
+    }
+}
+
 impl fmt::Display for Rover {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{} {} {}",
-            self.coordinates.x_coordinate, self.coordinates.y_coordinate, self.bearing
-        )
+        write!(f, "{} {}", self.coordinates, self.bearing)
+    }
+}
+
+/// Parses a pose written the way `Display` writes it, `x y bearing`, so one run's output
+/// can be the next run's input. The rover has no camera.
+impl FromStr for Rover {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(' ').collect::<Vec<_>>()[..] {
+            [x, y, bearing] => {
+                let coordinates = Coordinates::from_parts(x, y)?;
+                Ok(Rover::new(
+                    coordinates.x_coordinate,
+                    coordinates.y_coordinate,
+                    bearing.parse()?,
+                ))
+            }
+            _ => Err(format!("expected pose as 'x y bearing', found '{}'", s)),
+        }
     }
 }
 
 #[derive(Hash, Eq, PartialEq, PartialOrd, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Bearing {
+    #[cfg_attr(feature = "serde", serde(rename = "N"))]
     North,
+    #[cfg_attr(feature = "serde", serde(rename = "NE"))]
+    NorthEast,
+    #[cfg_attr(feature = "serde", serde(rename = "E"))]
     East,
+    #[cfg_attr(feature = "serde", serde(rename = "SE"))]
+    SouthEast,
+    #[cfg_attr(feature = "serde", serde(rename = "S"))]
     South,
+    #[cfg_attr(feature = "serde", serde(rename = "SW"))]
+    SouthWest,
+    #[cfg_attr(feature = "serde", serde(rename = "W"))]
     West,
+    #[cfg_attr(feature = "serde", serde(rename = "NW"))]
+    NorthWest,
 }
 
+impl Bearing {
+    /// Every bearing, clockwise from north in 45 degree steps.
+    pub const ALL: [Bearing; 8] = [
+        Bearing::North,
+        Bearing::NorthEast,
+        Bearing::East,
+        Bearing::SouthEast,
+        Bearing::South,
+        Bearing::SouthWest,
+        Bearing::West,
+        Bearing::NorthWest,
+    ];
+
+    /// Unit step taken when moving forward, as (x, y).
+    pub fn direction(&self) -> (i64, i64) {
+        match self {
+            Bearing::North => (0, 1),
+            Bearing::NorthEast => (1, 1),
+            Bearing::East => (1, 0),
+            Bearing::SouthEast => (1, -1),
+            Bearing::South => (0, -1),
+            Bearing::SouthWest => (-1, -1),
+            Bearing::West => (-1, 0),
+            Bearing::NorthWest => (-1, 1),
+        }
+    }
+
+    /// Compass degrees clockwise from north.
+    pub fn degrees(&self) -> u16 {
+        *self as u16 * 45
+    }
+
+    pub fn is_diagonal(&self) -> bool {
+        let (dx, dy) = self.direction();
+        dx != 0 && dy != 0
+    }
+
+    /// Rotates clockwise by the given number of 45 degree steps; negative steps turn left.
+    pub fn turned(self, eighths: i64) -> Bearing {
+        let index = (self as i64 + eighths).rem_euclid(Bearing::ALL.len() as i64);
+        Bearing::ALL[index as usize]
+    }
+}
+
+/// Which bearings and turns a mission allows. Four point is the default, matching the
+/// original challenge; eight point adds diagonal bearings and half turns.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Compass {
+    #[default]
+    FourPoint,
+    EightPoint {
+        allow_squeeze: bool,
+    },
+}
+
+/// Accepts short forms such as `N` and `ne`, long forms such as `North`, `NORTHEAST` and
+/// `south-west` in any case, and compass degrees in steps of 45 such as `0`, `90` and
+/// `225`.
 impl FromStr for Bearing {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "N" => Ok(Bearing::North),
-            "E" => Ok(Bearing::East),
-            "S" => Ok(Bearing::South),
-            "W" => Ok(Bearing::South),
-            _ => Err(String::from("could not parse bearing")),
-        }
+        let name = normalise_name(s);
+        let bearing = match name.as_str() {
+            "n" | "north" => Bearing::North,
+            "ne" | "northeast" => Bearing::NorthEast,
+            "e" | "east" => Bearing::East,
+            "se" | "southeast" => Bearing::SouthEast,
+            "s" | "south" => Bearing::South,
+            "sw" | "southwest" => Bearing::SouthWest,
+            "w" | "west" => Bearing::West,
+            "nw" | "northwest" => Bearing::NorthWest,
+            _ => match Bearing::ALL.iter().find(|b| b.degrees().to_string() == s) {
+                Some(bearing) => *bearing,
+                None => return Err(format!("unknown bearing '{}'", s)),
+            },
+        };
+        Ok(bearing)
     }
 }
 
+/// Lower cases a bearing or command name and drops the separators people put between its
+/// words, so `North-East`, `north east` and `NORTHEAST` all read the same.
+pub(crate) fn normalise_name(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
 impl fmt::Display for Bearing {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let b = match self {
             Bearing::North => "N",
+            Bearing::NorthEast => "NE",
             Bearing::East => "E",
+            Bearing::SouthEast => "SE",
             Bearing::South => "S",
+            Bearing::SouthWest => "SW",
             Bearing::West => "W",
+            Bearing::NorthWest => "NW",
         };
         write!(f, "{}", b)
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
+#[derive(Hash, Eq, PartialEq, PartialOrd, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Coordinates {
     pub x_coordinate: u64,
     pub y_coordinate: u64,
@@ -102,16 +376,55 @@ impl Coordinates {
         }
     }
 
-    pub fn move_forward(&mut self, bearing: Bearing) {
-        match bearing {
-            Bearing::North => self.y_coordinate += 1,
-            Bearing::South => self.y_coordinate -= 1,
-            Bearing::East => self.x_coordinate += 1,
-            Bearing::West => self.x_coordinate -= 1,
+    /// The cell `dx` and `dy` away, or `None` if it lies below zero or past `u64::MAX`.
+    pub fn step(&self, dx: i64, dy: i64) -> Option<Coordinates> {
+        Some(Coordinates::new(
+            offset(self.x_coordinate, dx)?,
+            offset(self.y_coordinate, dy)?,
+        ))
+    }
+
+    fn from_parts(x: &str, y: &str) -> Result<Coordinates, String> {
+        Ok(Coordinates::new(parse_coordinate(x)?, parse_coordinate(y)?))
+    }
+}
+
+fn offset(value: u64, by: i64) -> Option<u64> {
+    if by < 0 {
+        value.checked_sub(by.unsigned_abs())
+    } else {
+        value.checked_add(by as u64)
+    }
+}
+
+impl fmt::Display for Coordinates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.x_coordinate, self.y_coordinate)
+    }
+}
+
+/// Parses coordinates written the way `Display` writes them, `x y`, separated by a single
+/// space with nothing either side.
+impl FromStr for Coordinates {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(' ').collect::<Vec<_>>()[..] {
+            [x, y] => Coordinates::from_parts(x, y),
+            _ => Err(format!("expected coordinates as 'x y', found '{}'", s)),
         }
     }
 }
 
+/// Accepts plain digits only, unlike `u64::from_str`, which also takes a leading `+`.
+fn parse_coordinate(s: &str) -> Result<u64, String> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("'{}' is not a whole number", s));
+    }
+    s.parse()
+        .map_err(|_| format!("'{}' is too large for a coordinate", s))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,6 +465,24 @@ mod tests {
         assert_eq!(Rover::new(0, 0, Bearing::West), rover);
     }
 
+    #[test]
+    fn move_backward_facing_north() {
+        let mut rover = Rover::new(0, 1, Bearing::North);
+
+        rover.execute_command(&Command::MoveBackward);
+
+        assert_eq!(Rover::new(0, 0, Bearing::North), rover);
+    }
+
+    #[test]
+    fn move_backward_facing_west() {
+        let mut rover = Rover::new(0, 0, Bearing::West);
+
+        rover.execute_command(&Command::MoveBackward);
+
+        assert_eq!(Rover::new(1, 0, Bearing::West), rover);
+    }
+
     #[test]
     fn turn_right_from_north() {
         let mut rover = Rover::new(0, 0, Bearing::North);
@@ -170,14 +501,179 @@ mod tests {
         assert_eq!(Rover::new(0, 0, Bearing::West), rover);
     }
 
+    #[test]
+    fn turn_left_from_west() {
+        let mut rover = Rover::new(0, 0, Bearing::West);
+
+        rover.execute_command(&Command::LeftTurn);
+
+        assert_eq!(Rover::new(0, 0, Bearing::South), rover);
+    }
+
+    #[test]
+    fn half_turn_right_from_north() {
+        let mut rover = Rover::new(0, 0, Bearing::North);
+
+        rover.execute_command(&Command::HalfRightTurn);
+
+        assert_eq!(Rover::new(0, 0, Bearing::NorthEast), rover);
+    }
+
+    #[test]
+    fn half_turn_left_from_north() {
+        let mut rover = Rover::new(0, 0, Bearing::North);
+
+        rover.execute_command(&Command::HalfLeftTurn);
+
+        assert_eq!(Rover::new(0, 0, Bearing::NorthWest), rover);
+    }
+
+    #[test]
+    fn move_north_east() {
+        let mut rover = Rover::new(0, 0, Bearing::NorthEast);
+
+        rover.execute_command(&Command::MoveForward);
+
+        assert_eq!(Rover::new(1, 1, Bearing::NorthEast), rover);
+    }
+
+    #[test]
+    fn move_backward_facing_south_west() {
+        let mut rover = Rover::new(0, 0, Bearing::SouthWest);
+
+        rover.execute_command(&Command::MoveBackward);
+
+        assert_eq!(Rover::new(1, 1, Bearing::SouthWest), rover);
+    }
+
     #[test]
     fn get_planned_move() {
         let rover = Rover::new(0, 0, Bearing::North);
 
         let planned_coordinates = rover.planned_move();
 
-        assert_eq!(Coordinates::new(0, 1), planned_coordinates);
+        assert_eq!(Some(Coordinates::new(0, 1)), planned_coordinates);
 
         assert_eq!(Rover::new(0, 0, Bearing::North), rover);
     }
+
+    #[test]
+    fn get_planned_move_backward() {
+        let rover = Rover::new(1, 1, Bearing::East);
+
+        let planned_coordinates = rover.planned_move_backward();
+
+        assert_eq!(Some(Coordinates::new(0, 1)), planned_coordinates);
+
+        assert_eq!(Rover::new(1, 1, Bearing::East), rover);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_rover() {
+        let rover = Rover::new(1, 2, Bearing::NorthWest);
+
+        let json = serde_json::to_string(&rover).expect("should serialize");
+
+        assert_eq!(
+            r#"{"bearing":"NW","coordinates":{"x_coordinate":1,"y_coordinate":2},"camera":null}"#,
+            json
+        );
+        assert_eq!(
+            rover,
+            serde_json::from_str(&json).expect("should deserialize")
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_every_bearing_as_its_short_form() {
+        for bearing in Bearing::ALL.iter() {
+            let json = serde_json::to_string(bearing).expect("should serialize");
+
+            assert_eq!(format!("\"{}\"", bearing), json);
+            assert_eq!(*bearing, serde_json::from_str::<Bearing>(&json).unwrap());
+        }
+    }
+
+    #[test]
+    fn pose_round_trips_for_every_bearing() {
+        let corners = [(0, 0), (1, 2), (u64::MAX, 7)];
+        for bearing in Bearing::ALL.iter() {
+            for (x, y) in corners.iter() {
+                let rover = Rover::new(*x, *y, *bearing);
+
+                let text = rover.to_string();
+
+                assert_eq!(Ok(rover), text.parse::<Rover>());
+            }
+        }
+    }
+
+    #[test]
+    fn coordinates_round_trip() {
+        let coordinates = Coordinates::new(12, u64::MAX);
+
+        assert_eq!("12 18446744073709551615", coordinates.to_string());
+        assert_eq!(Ok(coordinates), coordinates.to_string().parse());
+    }
+
+    #[test]
+    fn reject_malformed_poses() {
+        let malformed = [
+            "",
+            "1 2",
+            "1 2 N M",
+            "1  2 N",
+            " 1 2 N",
+            "1 2 N ",
+            "-1 2 N",
+            "+1 2 N",
+            "1 x N",
+            "1 2 Q",
+            "1\t2 N",
+            "18446744073709551616 0 N",
+        ];
+        for pose in malformed.iter() {
+            assert!(pose.parse::<Rover>().is_err(), "accepted '{}'", pose);
+        }
+        assert!("1 2 3".parse::<Coordinates>().is_err());
+    }
+
+    #[test]
+    fn every_bearing_round_trips_through_every_name() {
+        let mut seen = vec![];
+        for (index, bearing) in Bearing::ALL.iter().enumerate() {
+            let short = bearing.to_string();
+            let long = format!("{:?}", bearing);
+            let names = [
+                short.clone(),
+                short.to_lowercase(),
+                long.clone(),
+                long.to_uppercase(),
+                long.to_lowercase(),
+                bearing.degrees().to_string(),
+            ];
+            for name in names.iter() {
+                assert_eq!(Ok(*bearing), name.parse::<Bearing>(), "parsing '{}'", name);
+            }
+            assert_eq!(index as u16 * 45, bearing.degrees());
+            assert!(!seen.contains(&short), "{} displayed twice", short);
+            seen.push(short);
+        }
+    }
+
+    #[test]
+    fn parse_separated_long_bearings() {
+        assert_eq!(Ok(Bearing::NorthEast), "North-East".parse());
+        assert_eq!(Ok(Bearing::SouthWest), "south west".parse());
+        assert_eq!(Ok(Bearing::West), "270".parse());
+    }
+
+    #[test]
+    fn reject_unknown_bearings() {
+        for name in ["", "NNE", "up", "30", "360", "-90"].iter() {
+            assert!(name.parse::<Bearing>().is_err(), "accepted '{}'", name);
+        }
+    }
 }
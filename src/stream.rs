@@ -0,0 +1,87 @@
+use crate::{Command, ParseError};
+use std::io::{BufReader, Bytes, Read};
+
+/// Reads commands one character at a time from a recorded command log, so a log of any
+/// size can be replayed without holding it in memory. Whitespace between commands is
+/// ignored; groups, counts and sensors are not allowed.
+pub struct CommandStream<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    offset: u64,
+}
+
+impl<R: Read> CommandStream<R> {
+    pub fn new(source: R) -> CommandStream<R> {
+        CommandStream {
+            bytes: BufReader::new(source).bytes(),
+            offset: 0,
+        }
+    }
+}
+
+impl<R: Read> Iterator for CommandStream<R> {
+    type Item = Result<Command, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let byte = match self.bytes.next()? {
+                Ok(byte) => byte,
+                Err(e) => {
+                    let details = format!("could not read command log: {}", e);
+                    return Some(Err(ParseError::new(&details)));
+                }
+            };
+            self.offset += 1;
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+            return Some(Command::new(byte as char).map_err(|_| {
+                ParseError::new(&format!(
+                    "unknown command '{}' at byte {}",
+                    byte.escape_ascii(),
+                    self.offset
+                ))
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_commands() {
+        let commands: Result<Vec<Command>, ParseError> =
+            CommandStream::new("MR\nL P\r\n".as_bytes()).collect();
+
+        assert_eq!(
+            vec![
+                Command::MoveForward,
+                Command::RightTurn,
+                Command::LeftTurn,
+                Command::TakePhoto
+            ],
+            commands.expect("should read commands")
+        );
+    }
+
+    #[test]
+    fn report_unknown_command() {
+        let mut stream = CommandStream::new("MM\nX".as_bytes());
+
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_ok());
+        match stream.next() {
+            Some(Err(e)) => assert_eq!("unknown command 'X' at byte 4", e.to_string()),
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reject_groups() {
+        let result: Result<Vec<Command>, ParseError> =
+            CommandStream::new("(M)2".as_bytes()).collect();
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use crate::plateau::{render_square, CollisionError, DropError, OutOfBounds, Plateau};
+use crate::rover::Coordinates;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// The ground rovers drive over: its bounds, what occupies it and how moves across it
+/// resolve. Implemented by every plateau shape so one engine can run them all.
+pub trait Terrain {
+    fn is_inbounds(&self, coordinates: &Coordinates) -> bool;
+
+    fn is_occupied(&self, coordinates: &Coordinates) -> bool;
+
+    /// Whether the cell is occupied by an obstacle rather than a rover.
+    fn has_obstacle(&self, coordinates: &Coordinates) -> bool;
+
+    /// Every cell a rover could stand on, bottom row first and left to right.
+    fn cells(&self) -> Vec<Coordinates>;
+
+    /// Lands a rover on the cell. Landing outside the terrain is `OutOfBounds`, and
+    /// landing on an occupied cell a `DropError`.
+    fn drop_rover(&mut self, coordinates: Coordinates) -> Result<(), Box<dyn Error>>;
+
+    fn place_obstacle(&mut self, coordinates: Coordinates) -> Result<(), DropError>;
+
+    fn can_rover_move(&self, coordinates: &Coordinates) -> Result<(), CollisionError>;
+
+    fn update_rover_position(
+        &mut self,
+        old_coordinates: &Coordinates,
+        new_coordinates: &Coordinates,
+    ) -> Result<(), Box<dyn Error>>;
+
+    fn render(&self) -> String;
+
+    /// The cell a step from `from` leads to, before it is checked, or `None` if it leads
+    /// below zero or past `u64::MAX`, which is out of bounds. Most terrains just add the
+    /// step, so a move off the edge stays out of bounds.
+    fn resolve(&self, from: &Coordinates, step: (i64, i64)) -> Option<Coordinates> {
+        from.step(step.0, step.1)
+    }
+
+    /// Whether every cell in the box from `min` to `max` is in bounds and empty, apart
+    /// from `except`. The default looks at each cell in turn.
+    fn is_clear(&self, min: &Coordinates, max: &Coordinates, except: &Coordinates) -> bool {
+        (min.y_coordinate..=max.y_coordinate).all(|y_coordinate| {
+            (min.x_coordinate..=max.x_coordinate).all(|x_coordinate| {
+                let cell = Coordinates::new(x_coordinate, y_coordinate);
+                cell == *except || (self.is_inbounds(&cell) && !self.is_occupied(&cell))
+            })
+        })
+    }
+
+    /// A diagonal move passes between the two cells that share an edge with both its start
+    /// and end. The move is blocked when both of those cells are occupied.
+    fn can_rover_squeeze(
+        &self,
+        from: &Coordinates,
+        to: &Coordinates,
+    ) -> Result<(), CollisionError> {
+        if from.x_coordinate == to.x_coordinate || from.y_coordinate == to.y_coordinate {
+            return Ok(());
+        }
+        let first = Coordinates::new(to.x_coordinate, from.y_coordinate);
+        let second = Coordinates::new(from.x_coordinate, to.y_coordinate);
+        if self.is_occupied(&first) && self.is_occupied(&second) {
+            return Err(CollisionError);
+        }
+        Ok(())
+    }
+}
+
+/// Whether the cell lies in the box from `min` to `max`.
+pub(crate) fn within(coordinates: &Coordinates, min: &Coordinates, max: &Coordinates) -> bool {
+    (min.x_coordinate..=max.x_coordinate).contains(&coordinates.x_coordinate)
+        && (min.y_coordinate..=max.y_coordinate).contains(&coordinates.y_coordinate)
+}
+
+/// A square plateau whose edges wrap around, so driving off one side arrives on the other.
+#[derive(Clone)]
+pub struct ToroidalPlateau {
+    plateau: Plateau,
+}
+
+impl ToroidalPlateau {
+    pub fn new(max_x_coordinate: u64, max_y_coordinate: u64) -> ToroidalPlateau {
+        ToroidalPlateau {
+            plateau: Plateau::new(max_x_coordinate, max_y_coordinate),
+        }
+    }
+}
+
+impl Terrain for ToroidalPlateau {
+    fn is_inbounds(&self, coordinates: &Coordinates) -> bool {
+        self.plateau.is_inbounds(coordinates)
+    }
+
+    fn is_occupied(&self, coordinates: &Coordinates) -> bool {
+        self.plateau.is_occupied(coordinates)
+    }
+
+    fn has_obstacle(&self, coordinates: &Coordinates) -> bool {
+        self.plateau.has_obstacle(coordinates)
+    }
+
+    fn cells(&self) -> Vec<Coordinates> {
+        Terrain::cells(&self.plateau)
+    }
+
+    fn drop_rover(&mut self, coordinates: Coordinates) -> Result<(), Box<dyn Error>> {
+        Terrain::drop_rover(&mut self.plateau, coordinates)
+    }
+
+    fn place_obstacle(&mut self, coordinates: Coordinates) -> Result<(), DropError> {
+        self.plateau.place_obstacle(coordinates)
+    }
+
+    fn can_rover_move(&self, coordinates: &Coordinates) -> Result<(), CollisionError> {
+        self.plateau.can_rover_move(coordinates)
+    }
+
+    fn update_rover_position(
+        &mut self,
+        old_coordinates: &Coordinates,
+        new_coordinates: &Coordinates,
+    ) -> Result<(), Box<dyn Error>> {
+        self.plateau
+            .update_rover_position(old_coordinates, new_coordinates)
+    }
+
+    fn render(&self) -> String {
+        self.plateau.render()
+    }
+
+    fn is_clear(&self, min: &Coordinates, max: &Coordinates, except: &Coordinates) -> bool {
+        self.plateau.is_clear(min, max, except)
+    }
+
+    /// Every step leads somewhere: off one edge it arrives on the opposite one.
+    fn resolve(&self, from: &Coordinates, step: (i64, i64)) -> Option<Coordinates> {
+        Some(Coordinates::new(
+            wrap(from.x_coordinate, step.0, self.plateau.max_x_coordinate()),
+            wrap(from.y_coordinate, step.1, self.plateau.max_y_coordinate()),
+        ))
+    }
+}
+
+/// Steps a coordinate in `0..=max` by one cell at most, wrapping off either edge onto
+/// the other.
+fn wrap(coordinate: u64, step: i64, max: u64) -> u64 {
+    if step < 0 {
+        coordinate.checked_sub(1).unwrap_or(max)
+    } else if step > 0 && coordinate < max {
+        coordinate + 1
+    } else if step > 0 {
+        0
+    } else {
+        coordinate
+    }
+}
+
+/// A square plateau with cells cut out of it, such as craters. Masked cells are out of
+/// bounds rather than obstacles, so they neither take part in coverage nor block cameras.
+#[derive(Clone)]
+pub struct MaskedPlateau {
+    plateau: Plateau,
+    masked: HashSet<Coordinates>,
+}
+
+impl MaskedPlateau {
+    pub fn new(
+        max_x_coordinate: u64,
+        max_y_coordinate: u64,
+        masked: impl IntoIterator<Item = Coordinates>,
+    ) -> MaskedPlateau {
+        MaskedPlateau {
+            plateau: Plateau::new(max_x_coordinate, max_y_coordinate),
+            masked: masked.into_iter().collect(),
+        }
+    }
+}
+
+impl Terrain for MaskedPlateau {
+    fn is_inbounds(&self, coordinates: &Coordinates) -> bool {
+        self.plateau.is_inbounds(coordinates) && !self.masked.contains(coordinates)
+    }
+
+    fn is_occupied(&self, coordinates: &Coordinates) -> bool {
+        self.plateau.is_occupied(coordinates)
+    }
+
+    fn has_obstacle(&self, coordinates: &Coordinates) -> bool {
+        self.plateau.has_obstacle(coordinates)
+    }
+
+    fn cells(&self) -> Vec<Coordinates> {
+        Terrain::cells(&self.plateau)
+            .into_iter()
+            .filter(|c| !self.masked.contains(c))
+            .collect()
+    }
+
+    fn drop_rover(&mut self, coordinates: Coordinates) -> Result<(), Box<dyn Error>> {
+        if self.masked.contains(&coordinates) {
+            return Err(Box::new(OutOfBounds));
+        }
+        Terrain::drop_rover(&mut self.plateau, coordinates)
+    }
+
+    fn place_obstacle(&mut self, coordinates: Coordinates) -> Result<(), DropError> {
+        if self.masked.contains(&coordinates) {
+            return Err(DropError);
+        }
+        self.plateau.place_obstacle(coordinates)
+    }
+
+    fn can_rover_move(&self, coordinates: &Coordinates) -> Result<(), CollisionError> {
+        self.plateau.can_rover_move(coordinates)
+    }
+
+    fn update_rover_position(
+        &mut self,
+        old_coordinates: &Coordinates,
+        new_coordinates: &Coordinates,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.masked.contains(new_coordinates) {
+            return Err(Box::new(OutOfBounds));
+        }
+        self.plateau
+            .update_rover_position(old_coordinates, new_coordinates)
+    }
+
+    fn is_clear(&self, min: &Coordinates, max: &Coordinates, except: &Coordinates) -> bool {
+        self.plateau.is_clear(min, max, except) && !self.masked.iter().any(|c| within(c, min, max))
+    }
+
+    fn render(&self) -> String {
+        render_square(&self.plateau, |c| {
+            if self.masked.contains(c) {
+                'x'
+            } else {
+                self.plateau.symbol(c)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toroidal_plateau_wraps_off_the_edge() {
+        let plateau = ToroidalPlateau::new(4, 4);
+        let origin = Coordinates::new(0, 0);
+
+        assert_eq!(
+            Some(Coordinates::new(4, 4)),
+            plateau.resolve(&origin, (-1, -1))
+        );
+        assert_eq!(
+            Some(Coordinates::new(0, 2)),
+            plateau.resolve(&Coordinates::new(4, 2), (1, 0))
+        );
+    }
+
+    #[test]
+    fn toroidal_plateau_wraps_at_the_widest_sizes() {
+        let origin = Coordinates::new(0, 0);
+        for max in [u64::MAX, u64::MAX - 1, i64::MAX as u64 + 1].iter() {
+            let plateau = ToroidalPlateau::new(*max, 4);
+
+            assert_eq!(
+                Some(Coordinates::new(*max, 4)),
+                plateau.resolve(&origin, (-1, -1))
+            );
+            assert_eq!(
+                Some(Coordinates::new(0, 0)),
+                plateau.resolve(&Coordinates::new(*max, 4), (1, 1))
+            );
+            assert_eq!(
+                Some(Coordinates::new(*max - 1, 4)),
+                plateau.resolve(&Coordinates::new(*max, 4), (-1, 0))
+            );
+        }
+    }
+
+    #[test]
+    fn step_off_the_widest_plateau_out_of_bounds() {
+        let plateau = Plateau::new(u64::MAX, u64::MAX);
+
+        assert_eq!(None, plateau.resolve(&Coordinates::new(0, 0), (-1, 0)));
+        assert_eq!(
+            None,
+            plateau.resolve(&Coordinates::new(u64::MAX, 0), (1, 0))
+        );
+    }
+
+    #[test]
+    fn masked_cells_are_out_of_bounds() {
+        let mut plateau = MaskedPlateau::new(2, 2, vec![Coordinates::new(1, 1)]);
+
+        assert!(!plateau.is_inbounds(&Coordinates::new(1, 1)));
+        assert_eq!(8, plateau.cells().len());
+        assert!(plateau
+            .drop_rover(Coordinates::new(1, 1))
+            .unwrap_err()
+            .is::<OutOfBounds>());
+
+        if let Err(e) = plateau.drop_rover(Coordinates::new(0, 1)) {
+            panic!("should be able to drop rover : {}", e)
+        };
+
+        let moved = plateau.update_rover_position(&Coordinates::new(0, 1), &Coordinates::new(1, 1));
+        assert!(moved.is_err());
+    }
+
+    #[test]
+    fn render_masked_plateau() {
+        let plateau = MaskedPlateau::new(2, 1, vec![Coordinates::new(2, 0)]);
+
+        assert_eq!(". . .\n. . x", plateau.render());
+    }
+}
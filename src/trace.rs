@@ -0,0 +1,165 @@
+use crate::rover::{Bearing, Coordinates};
+use crate::{Command, Status};
+use serde_json::json;
+use std::io;
+use std::io::Write;
+
+/// One command carried out, or attempted, by a rover, with the pose it left the rover in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    pub tick: u64,
+    /// The rover's mission id, or its landing index if it has none, as the binary's JSON
+    /// output names it.
+    pub rover: String,
+    pub command: Command,
+    pub coordinates: Coordinates,
+    pub bearing: Bearing,
+    pub outcome: Status,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    records: Vec<TraceRecord>,
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Trace::default()
+    }
+
+    pub fn record(&mut self, record: TraceRecord) {
+        self.records.push(record);
+    }
+
+    pub fn records(&self) -> &[TraceRecord] {
+        &self.records
+    }
+
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "tick,rover,command,x,y,bearing,outcome")?;
+        for record in &self.records {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                record.tick,
+                csv_field(&record.rover),
+                record.command,
+                record.coordinates.x_coordinate,
+                record.coordinates.y_coordinate,
+                record.bearing,
+                record.outcome
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes one JSON object per line, so large traces can be read a record at a time.
+    pub fn write_ndjson<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for record in &self.records {
+            let line = json!({
+                "tick": record.tick,
+                "rover": record.rover,
+                "command": record.command.to_string(),
+                "x": record.coordinates.x_coordinate,
+                "y": record.coordinates.y_coordinate,
+                "bearing": record.bearing.to_string(),
+                "outcome": record.outcome.to_string(),
+            });
+            serde_json::to_writer(&mut *writer, &line)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Quotes a field that would otherwise break the CSV row.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace() -> Trace {
+        let mut trace = Trace::new();
+        trace.record(TraceRecord {
+            tick: 0,
+            rover: String::from("spirit"),
+            command: Command::MoveForward,
+            coordinates: Coordinates::new(1, 3),
+            bearing: Bearing::North,
+            outcome: Status::Ok,
+        });
+        trace.record(TraceRecord {
+            tick: 1,
+            rover: String::from("spirit"),
+            command: Command::MoveForward,
+            coordinates: Coordinates::new(1, 3),
+            bearing: Bearing::North,
+            outcome: Status::Lost,
+        });
+        trace
+    }
+
+    #[test]
+    fn export_csv() {
+        let mut output = vec![];
+
+        trace().write_csv(&mut output).expect("should write csv");
+
+        assert_eq!(
+            "tick,rover,command,x,y,bearing,outcome\n0,spirit,M,1,3,N,ok\n1,spirit,M,1,3,N,lost\n",
+            String::from_utf8(output).expect("should be utf8")
+        );
+    }
+
+    #[test]
+    fn quote_rover_ids_in_csv() {
+        let mut trace = Trace::new();
+        trace.record(TraceRecord {
+            rover: String::from("mer-a, \"spirit\""),
+            ..self::trace().records()[0].clone()
+        });
+        let mut output = vec![];
+
+        trace.write_csv(&mut output).expect("should write csv");
+
+        assert_eq!(
+            "tick,rover,command,x,y,bearing,outcome\n0,\"mer-a, \"\"spirit\"\"\",M,1,3,N,ok\n",
+            String::from_utf8(output).expect("should be utf8")
+        );
+    }
+
+    #[test]
+    fn export_ndjson() {
+        let mut output = vec![];
+
+        trace()
+            .write_ndjson(&mut output)
+            .expect("should write ndjson");
+
+        let output = String::from_utf8(output).expect("should be utf8");
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("should be valid json"))
+            .collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(
+            json!({
+                "tick": 1,
+                "rover": "spirit",
+                "command": "M",
+                "x": 1,
+                "y": 3,
+                "bearing": "N",
+                "outcome": "lost",
+            }),
+            lines[1]
+        );
+    }
+}
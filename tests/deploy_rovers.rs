@@ -1,6 +1,17 @@
-use mars_rover;
-use mars_rover::rover::{Bearing, Rover};
-use mars_rover::Config;
+use mars_rover::camera::{Camera, FieldOfView};
+use mars_rover::hex;
+use mars_rover::rover::{Bearing, Compass, Coordinates, Rover};
+use mars_rover::terrain::{MaskedPlateau, ToroidalPlateau};
+use mars_rover::{Config, Status};
+use std::io::Read;
+
+fn config(args: &[&str]) -> Config {
+    let args: Vec<String> = std::iter::once("test")
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect();
+    Config::new(&args).expect("should create config")
+}
 
 #[test]
 fn it_deploys_rover() {
@@ -29,3 +40,386 @@ fn it_deploys_rover() {
 
     assert_eq!(rovers[1], second_expected);
 }
+
+#[test]
+fn it_reports_coverage() {
+    let config = config(&["2", "2", "0", "0", "N", "MM", "2", "2", "S", "M"]);
+
+    let deployment = mars_rover::deploy(config).expect("should deploy rovers");
+    let report = deployment.coverage_report();
+
+    assert_eq!(9, report.total_cells);
+    assert_eq!(5, report.covered_cells);
+    assert_eq!(
+        vec![
+            Coordinates::new(0, 0),
+            Coordinates::new(0, 1),
+            Coordinates::new(0, 2)
+        ],
+        deployment.coverage().visited_by(0)
+    );
+    assert!(report.overlap.is_empty());
+}
+
+#[test]
+fn it_reports_imaged_cells() {
+    let config = config(&["3", "3", "0", "0", "N", "M"])
+        .with_obstacle(Coordinates::new(0, 2))
+        .with_camera(Camera::new(2, FieldOfView::Cone));
+
+    let deployment = mars_rover::deploy(config).expect("should deploy rovers");
+    let imaged = deployment.coverage().imaged_by(0);
+
+    assert!(imaged.contains(&Coordinates::new(0, 2)));
+    assert!(imaged.contains(&Coordinates::new(1, 2)));
+    assert!(!imaged.contains(&Coordinates::new(0, 3)));
+}
+
+#[test]
+fn it_logs_photos() {
+    let config =
+        config(&["3", "3", "0", "0", "N", "MPRP"]).with_camera(Camera::new(1, FieldOfView::Cone));
+
+    let deployment = mars_rover::deploy(config).expect("should deploy rovers");
+    let photos = deployment.photos().records();
+
+    assert_eq!(2, photos.len());
+    assert_eq!(1, photos[0].tick);
+    assert_eq!(Bearing::North, photos[0].bearing);
+    assert_eq!(
+        vec![Coordinates::new(0, 1), Coordinates::new(0, 2)],
+        photos[0].imaged
+    );
+    assert_eq!(3, photos[1].tick);
+    assert_eq!(Bearing::East, photos[1].bearing);
+}
+
+#[test]
+fn it_reverses_rover() {
+    let config = config(&["5", "5", "2", "2", "E", "BBLB"]);
+
+    let rovers = mars_rover::deploy_rovers(config).expect("should deploy rovers");
+
+    assert_eq!(rovers[0], Rover::new(0, 1, Bearing::North));
+}
+
+#[test]
+fn it_stops_rover_reversing_off_plateau() {
+    let config = config(&["5", "5", "0", "0", "N", "B"]);
+
+    assert!(mars_rover::deploy_rovers(config).is_err());
+}
+
+#[test]
+fn it_moves_diagonally_with_eight_point_compass() {
+    let config = config(&["5", "5", "0", "0", "NE", "MM>M"]).with_compass(Compass::EightPoint {
+        allow_squeeze: true,
+    });
+
+    let rovers = mars_rover::deploy_rovers(config).expect("should deploy rovers");
+
+    assert_eq!(rovers[0], Rover::new(3, 2, Bearing::East));
+}
+
+#[test]
+fn it_rejects_half_turns_with_four_point_compass() {
+    let config = config(&["5", "5", "0", "0", "N", ">M"]);
+
+    assert!(mars_rover::deploy_rovers(config).is_err());
+}
+
+#[test]
+fn it_stops_rover_squeezing_between_obstacles() {
+    let config = config(&["5", "5", "0", "0", "NE", "M"])
+        .with_obstacle(Coordinates::new(0, 1))
+        .with_obstacle(Coordinates::new(1, 0));
+
+    let squeezing = config.clone().with_compass(Compass::EightPoint {
+        allow_squeeze: true,
+    });
+    let blocked = config.with_compass(Compass::EightPoint {
+        allow_squeeze: false,
+    });
+
+    assert!(mars_rover::deploy_rovers(squeezing).is_ok());
+    assert!(mars_rover::deploy_rovers(blocked).is_err());
+}
+
+#[test]
+fn it_deploys_rovers_on_hex_grid() {
+    let config = config(&["3", "3", "0", "0", "N", "MRMRM", "3", "3", "S", "RM"]);
+
+    let deployment = hex::deploy(config).expect("should deploy rovers");
+    let rovers = deployment.rovers();
+
+    assert_eq!("2 0 SE", rovers[0].to_string());
+    assert_eq!("2 3 SW", rovers[1].to_string());
+    assert_eq!(2, deployment.plateau().list_rovers().len());
+}
+
+#[test]
+fn it_wraps_rovers_on_toroidal_plateau() {
+    let config = config(&["5", "5", "0", "0", "S", "RM"]);
+    let plateau = ToroidalPlateau::new(config.max_x_grid(), config.max_y_grid());
+
+    let deployment = mars_rover::drive::<_, Rover>(plateau, config).expect("should deploy rovers");
+
+    assert_eq!(deployment.rovers()[0], Rover::new(5, 0, Bearing::West));
+}
+
+#[test]
+fn it_keeps_rovers_off_masked_cells() {
+    let config = config(&["5", "5", "0", "0", "N", "MM"]);
+    let plateau = MaskedPlateau::new(5, 5, vec![Coordinates::new(0, 2)]);
+
+    assert!(mars_rover::drive::<_, Rover>(plateau, config).is_err());
+}
+
+#[test]
+fn it_drives_rovers_with_sensors() {
+    let config = config(&[
+        "5",
+        "5",
+        "0",
+        "0",
+        "N",
+        "while not edge { if blocked { R } else { M } }",
+    ])
+    .with_obstacle(Coordinates::new(0, 3));
+
+    let rovers = mars_rover::deploy_rovers(config).expect("should deploy rovers");
+
+    assert_eq!(rovers[0], Rover::new(5, 2, Bearing::East));
+}
+
+#[test]
+fn it_stops_programs_that_exceed_budget() {
+    let config = config(&["5", "5", "0", "0", "N", "while not blocked { R }"]).with_budget(1000);
+
+    assert!(mars_rover::deploy_rovers(config).is_err());
+}
+
+#[test]
+fn it_optimises_without_changing_the_route() {
+    let config = config(&[
+        "5",
+        "5",
+        "1",
+        "2",
+        "N",
+        "MRRRRM(LR)3RRRMLLLLRRRM",
+        "3",
+        "3",
+        "E",
+        "MMRMMRMRRM",
+    ]);
+    let mut optimised = config.clone();
+    let savings = optimised.optimise::<Rover>().expect("should optimise");
+
+    assert_eq!(18, savings[0].saved());
+    assert_eq!(0, savings[1].saved());
+
+    let expected = mars_rover::deploy(config).expect("should deploy rovers");
+    let actual = mars_rover::deploy(optimised).expect("should deploy rovers");
+
+    assert_eq!(expected.rovers(), actual.rovers());
+    for rover in 0..2 {
+        assert_eq!(
+            expected.coverage().visited_by(rover),
+            actual.coverage().visited_by(rover)
+        );
+    }
+}
+
+#[test]
+fn it_fast_forwards_to_the_same_result_as_stepping() {
+    let programs = [
+        "(MRMRMRMR)1000 M3",
+        "M(RM)3 M4",
+        "(M)10",
+        "MM(RRMM)2 while not edge { M }",
+        "MMRMMRMRRM",
+    ];
+    for program in programs.iter() {
+        let config = config(&["5", "5", "1", "2", "N", program, "3", "3", "E", "MR"])
+            .with_obstacle(Coordinates::new(1, 5))
+            .with_budget(5000);
+
+        let poses = |rovers: &[Rover]| rovers.iter().map(Rover::to_string).collect::<Vec<_>>();
+        let expected = mars_rover::deploy(config.clone()).map(|d| poses(d.rovers()));
+        let actual = mars_rover::deploy_rovers(config).map(|rovers| poses(&rovers));
+
+        match (expected, actual) {
+            (Ok(expected), Ok(actual)) => assert_eq!(expected, actual, "program {}", program),
+            (Err(expected), Err(actual)) => {
+                assert_eq!(
+                    expected.to_string(),
+                    actual.to_string(),
+                    "program {}",
+                    program
+                )
+            }
+            (expected, actual) => panic!("program {}: {:?} != {:?}", program, expected, actual),
+        }
+    }
+}
+
+#[test]
+fn it_fast_forwards_long_soak_programs() {
+    let config =
+        config(&["5", "5", "1", "2", "N", "((MRMRMRMR)1000000)1000 M"]).with_budget(u64::MAX);
+
+    let rovers = mars_rover::deploy_rovers(config).expect("should deploy rovers");
+
+    assert_eq!(rovers[0], Rover::new(1, 3, Bearing::North));
+}
+
+#[test]
+fn it_leaves_coverage_empty_without_coverage() {
+    let config = config(&["5", "5", "0", "0", "N", "while not edge { M } RMMM"])
+        .with_camera(Camera::new(2, FieldOfView::Cone))
+        .without_coverage();
+
+    let deployment = mars_rover::deploy(config).expect("should deploy rovers");
+
+    assert_eq!("3 5 E", deployment.rovers()[0].to_string());
+    assert!(deployment.coverage().visited_by(0).is_empty());
+    assert!(deployment.coverage().imaged_by(0).is_empty());
+}
+
+#[test]
+fn it_fast_forwards_on_any_terrain_without_losing_count() {
+    let config = config(&["5", "5", "1", "0", "N", "(RRRR)1000 P M(R4)2M P MM"]);
+    let masked = || MaskedPlateau::new(5, 5, vec![Coordinates::new(1, 4)]);
+
+    let stepped = mars_rover::drive_tolerant::<_, Rover>(masked(), config.clone())
+        .expect("should deploy rovers");
+    let fast = mars_rover::drive_tolerant::<_, Rover>(masked(), config.without_coverage())
+        .expect("should deploy rovers");
+
+    assert_eq!(stepped.rovers(), fast.rovers());
+    assert_eq!(Status::Lost, fast.outcomes()[0].status);
+    assert_eq!(stepped.outcomes(), fast.outcomes());
+    let ticks = |photos: &[mars_rover::photo::PhotoRecord]| {
+        photos.iter().map(|photo| photo.tick).collect::<Vec<_>>()
+    };
+    assert_eq!(vec![4000, 4011], ticks(fast.photos().records()));
+    assert_eq!(
+        ticks(stepped.photos().records()),
+        ticks(fast.photos().records())
+    );
+}
+
+#[test]
+fn it_replays_streamed_command_logs() {
+    let config = config(&["5", "5", "1", "2", "N", "M", "3", "3", "E", "M"]);
+
+    let log = std::io::repeat(b'R')
+        .take(1_000_002)
+        .chain("\nMM".as_bytes());
+    let plateau = || mars_rover::plateau::Plateau::new(5, 5);
+    let rovers: Vec<Rover> =
+        mars_rover::replay(plateau(), config.clone(), 1, log).expect("should replay log");
+
+    assert_eq!(rovers[0], Rover::new(1, 3, Bearing::North));
+    assert_eq!(rovers[1], Rover::new(2, 3, Bearing::West));
+
+    let replay = |rover, log: &str| {
+        mars_rover::replay::<_, Rover, _>(plateau(), config.clone(), rover, log.as_bytes())
+    };
+    assert!(replay(0, "MMM").is_err());
+    assert!(replay(2, "M").is_err());
+}
+
+#[test]
+fn it_replays_on_the_terrain_it_is_given() {
+    let config = config(&["3", "3", "0", "0", "E", "M"]);
+
+    let wrapped: Vec<Rover> = mars_rover::replay(
+        ToroidalPlateau::new(3, 3),
+        config.clone(),
+        0,
+        "MMM".as_bytes(),
+    )
+    .expect("should replay on toroidal terrain");
+    let hex: Result<Vec<hex::HexRover>, _> =
+        mars_rover::replay(hex::HexPlateau::new(3, 3), config, 0, "M".as_bytes());
+
+    assert_eq!(Rover::new(0, 0, Bearing::East), wrapped[0]);
+    assert!(hex.is_err());
+}
+
+#[test]
+fn it_refuses_to_land_rovers_off_the_terrain() {
+    let config = config(&["5", "5", "9", "9", "N", "RRRR"]);
+
+    let square = mars_rover::drive_tolerant::<_, Rover>(
+        mars_rover::plateau::Plateau::new(5, 5),
+        config.clone(),
+    )
+    .expect("should deploy rovers");
+    let toroidal = mars_rover::drive_tolerant::<_, Rover>(ToroidalPlateau::new(5, 5), config)
+        .expect("should deploy rovers");
+
+    assert_eq!(Status::Lost, square.outcomes()[0].status);
+    assert_eq!(Status::Lost, toroidal.outcomes()[0].status);
+    assert_eq!(0, toroidal.outcomes()[0].commands);
+}
+
+#[test]
+fn it_reports_each_rovers_outcome() {
+    let config = config(&[
+        "5",
+        "5",
+        "1",
+        "2",
+        "N",
+        "MMMMM",
+        "3",
+        "3",
+        "E",
+        "MMRMMRMRRM",
+        "4",
+        "3",
+        "N",
+        "LM",
+    ]);
+
+    assert!(mars_rover::deploy(config.clone()).is_err());
+
+    let plateau = mars_rover::plateau::Plateau::new(5, 5);
+    let deployment =
+        mars_rover::drive_tolerant::<_, Rover>(plateau, config).expect("should deploy rovers");
+    let outcomes = deployment.outcomes();
+
+    assert_eq!(Status::Lost, outcomes[0].status);
+    assert_eq!(3, outcomes[0].commands);
+    assert_eq!(deployment.rovers()[0], Rover::new(1, 5, Bearing::North));
+    assert_eq!(Status::Ok, outcomes[1].status);
+    assert_eq!(None, outcomes[1].error);
+    assert_eq!(Status::Ok, outcomes[2].status);
+    assert_eq!(deployment.rovers()[2], Rover::new(3, 3, Bearing::West));
+}
+
+#[test]
+fn it_traces_every_command() {
+    let config = config(&["5", "5", "1", "2", "N", "MR", "3", "3", "E", "MM"]).with_trace();
+
+    let plateau = mars_rover::plateau::Plateau::new(5, 5);
+    let deployment =
+        mars_rover::drive_tolerant::<_, Rover>(plateau, config).expect("should deploy rovers");
+    let mut output = vec![];
+    deployment
+        .trace()
+        .write_csv(&mut output)
+        .expect("should write csv");
+
+    assert_eq!(
+        "tick,rover,command,x,y,bearing,outcome\n\
+         0,0,M,1,3,N,ok\n\
+         1,0,R,1,3,E,ok\n\
+         2,1,M,4,3,E,ok\n\
+         3,1,M,5,3,E,ok\n",
+        String::from_utf8(output).expect("should be utf8")
+    );
+}